@@ -0,0 +1,143 @@
+use crate::LeaseInfo;
+use std::{cmp::max, time::Duration};
+use time::OffsetDateTime;
+
+/// How [`crate::Client::acquire`] & [`crate::Client::acquire_timeout`] wait between
+/// attempts while a lease is held elsewhere, see [`crate::ClientBuilder::acquire_strategy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum AcquireStrategy {
+    /// Retry every [`crate::ClientBuilder::acquire_cooldown`].
+    #[default]
+    Poll,
+    /// Use the current holder's expiry, returned by each failed attempt, to schedule
+    /// the next attempt.
+    ///
+    /// * If the holder's lease expires within the cooldown, retries as soon as it expires.
+    /// * Otherwise waits for half the time until expiry, but at least the cooldown and
+    ///   at most `max_cooldown`. So holders with distant expiry are polled less often.
+    /// * [`crate::Client::acquire_timeout`] returns [`crate::Error::Timeout`] early if the
+    ///   holder's lease will not expire before the max wait, i.e. it would only be acquired
+    ///   if released early.
+    ///
+    /// Leases released early are only observed at the next attempt, so may be acquired
+    /// later than with a fixed cooldown.
+    ExpiryAware {
+        /// Max time between attempts.
+        max_cooldown: Duration,
+    },
+}
+
+impl AcquireStrategy {
+    /// Returns how long to wait before the next attempt, after an attempt observed `holder`.
+    pub(crate) fn cooldown(
+        &self,
+        cooldown: Duration,
+        holder: Option<&LeaseInfo>,
+        now: OffsetDateTime,
+    ) -> Duration {
+        let (Self::ExpiryAware { max_cooldown }, Some(holder)) = (self, holder) else {
+            return cooldown;
+        };
+        match until_acquirable(holder, now) {
+            Duration::ZERO => cooldown,
+            until if until <= cooldown => until,
+            until => (until / 2).clamp(cooldown, max(*max_cooldown, cooldown)),
+        }
+    }
+
+    /// Returns `true` if waiting for `holder` to expire will take longer than `remaining_wait`,
+    /// so the acquisition should give up early.
+    pub(crate) fn outlives(
+        &self,
+        holder: Option<&LeaseInfo>,
+        remaining_wait: Duration,
+        now: OffsetDateTime,
+    ) -> bool {
+        match (self, holder) {
+            (Self::ExpiryAware { .. }, Some(holder)) => {
+                until_acquirable(holder, now) > remaining_wait
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Returns the time until `holder`'s lease may be replaced, i.e. `lease_expiry < now`.
+fn until_acquirable(holder: &LeaseInfo, now: OffsetDateTime) -> Duration {
+    let acquirable = OffsetDateTime::from_unix_timestamp(holder.expiry.saturating_add(1))
+        .unwrap_or(OffsetDateTime::UNIX_EPOCH);
+    (acquirable - now).try_into().unwrap_or(Duration::ZERO)
+}
+
+/// Per acquisition overrides of client config, see [`crate::Client::acquire_with`].
+///
+/// Unset options use the client's config.
+///
+/// # Example
+/// ```
+/// # use std::time::Duration;
+/// let options = dynamodb_lease::AcquireOptions::default()
+///     .lease_ttl_seconds(2 * 60 * 60)
+///     .timeout(Duration::from_secs(30));
+/// ```
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct AcquireOptions {
+    /// Overrides [`crate::ClientBuilder::lease_ttl_seconds`].
+    pub lease_ttl_seconds: Option<u32>,
+    /// Overrides [`crate::ClientBuilder::extend_every`].
+    pub extend_period: Option<Duration>,
+    /// Overrides [`crate::ClientBuilder::auto_extend`].
+    pub auto_extend: Option<bool>,
+    /// Overrides [`crate::ClientBuilder::acquire_cooldown`].
+    pub acquire_cooldown: Option<Duration>,
+    /// Max time to wait to acquire, like [`crate::Client::acquire_timeout`].
+    pub timeout: Option<Duration>,
+}
+
+impl AcquireOptions {
+    /// Sets the lease ttl, must be at least 2s.
+    ///
+    /// If set without [`AcquireOptions::extend_every`] leases are extended every `ttl / 2`.
+    ///
+    /// Default client [`crate::ClientBuilder::lease_ttl_seconds`].
+    pub fn lease_ttl_seconds(mut self, seconds: u32) -> Self {
+        self.lease_ttl_seconds = Some(seconds);
+        self
+    }
+
+    /// Sets the periodic duration between each background attempt to extend the lease,
+    /// must be greater than zero & less than the ttl.
+    ///
+    /// Default client [`crate::ClientBuilder::extend_every`].
+    pub fn extend_every(mut self, extend_period: Duration) -> Self {
+        self.extend_period = Some(extend_period);
+        self
+    }
+
+    /// Sets whether the lease is automatically extended in the background, otherwise it
+    /// must be explicitly extended with [`crate::Lease::extend`].
+    ///
+    /// Default client [`crate::ClientBuilder::auto_extend`].
+    pub fn auto_extend(mut self, auto_extend: bool) -> Self {
+        self.auto_extend = Some(auto_extend);
+        self
+    }
+
+    /// Sets how long to wait between attempts to acquire the lease.
+    ///
+    /// Default client [`crate::ClientBuilder::acquire_cooldown`].
+    pub fn acquire_cooldown(mut self, cooldown: Duration) -> Self {
+        self.acquire_cooldown = Some(cooldown);
+        self
+    }
+
+    /// Sets the max time to wait to acquire the lease.
+    ///
+    /// Default none, i.e. wait until acquired.
+    pub fn timeout(mut self, max_wait: Duration) -> Self {
+        self.timeout = Some(max_wait);
+        self
+    }
+}
@@ -1,5 +1,10 @@
-use crate::Client;
-use std::{sync::Arc, time::Duration};
+use crate::{
+    AcquireStrategy, Client, DynamoDbStore, Error, KeySplit, LeaseStore, RetryPolicy, TableOptions,
+    clock::{Clock, SkewEstimate, SkewInterceptor},
+    dynamodb::{AttributeNames, SortKey},
+};
+use std::{collections::BTreeMap, process, sync::Arc, time::Duration};
+use uuid::Uuid;
 
 /// [`Client`] builder.
 #[derive(Debug)]
@@ -7,7 +12,19 @@ pub struct ClientBuilder {
     table_name: String,
     lease_ttl_seconds: u32,
     extend_period: Option<Duration>,
+    auto_extend: bool,
     acquire_cooldown: Duration,
+    fair_acquire: bool,
+    acquire_strategy: AcquireStrategy,
+    extend_retry: RetryPolicy,
+    release_retry: RetryPolicy,
+    table_options: TableOptions,
+    attribute_names: AttributeNames,
+    sort_key: Option<SortKey>,
+    owner: Option<String>,
+    lease_attributes: BTreeMap<String, String>,
+    clock_skew_warn_threshold: Duration,
+    correct_clock_skew: bool,
 }
 
 impl Default for ClientBuilder {
@@ -16,7 +33,19 @@ impl Default for ClientBuilder {
             table_name: "leases".into(),
             lease_ttl_seconds: 60,
             extend_period: None,
+            auto_extend: true,
             acquire_cooldown: Duration::from_secs(1),
+            fair_acquire: false,
+            acquire_strategy: <_>::default(),
+            extend_retry: <_>::default(),
+            release_retry: <_>::default(),
+            table_options: <_>::default(),
+            attribute_names: <_>::default(),
+            sort_key: None,
+            owner: None,
+            lease_attributes: <_>::default(),
+            clock_skew_warn_threshold: Duration::from_secs(2),
+            correct_clock_skew: false,
         }
     }
 }
@@ -31,6 +60,122 @@ impl ClientBuilder {
         self
     }
 
+    /// Sets the name of the lease table string hash key attribute.
+    ///
+    /// Default `"key"`.
+    pub fn key_attribute_name(mut self, name: impl Into<String>) -> Self {
+        self.attribute_names.key = name.into();
+        self
+    }
+
+    /// Sets the name of the lease expiry attribute. Table time to live must be enabled
+    /// for this attribute.
+    ///
+    /// Default `"lease_expiry"`.
+    pub fn lease_expiry_attribute_name(mut self, name: impl Into<String>) -> Self {
+        self.attribute_names.lease_expiry = name.into();
+        self
+    }
+
+    /// Sets the name of the lease version attribute.
+    ///
+    /// Default `"lease_version"`.
+    pub fn lease_version_attribute_name(mut self, name: impl Into<String>) -> Self {
+        self.attribute_names.lease_version = name.into();
+        self
+    }
+
+    /// Sets the name of the lease generation attribute, used for fencing tokens.
+    ///
+    /// Default `"lease_generation"`.
+    pub fn lease_generation_attribute_name(mut self, name: impl Into<String>) -> Self {
+        self.attribute_names.lease_generation = name.into();
+        self
+    }
+
+    /// Sets the name of the lease owner attribute.
+    ///
+    /// Default `"lease_owner"`.
+    pub fn lease_owner_attribute_name(mut self, name: impl Into<String>) -> Self {
+        self.attribute_names.lease_owner = name.into();
+        self
+    }
+
+    /// Sets the name of the attribute recording who revoked a lease,
+    /// see [`Client::force_release`].
+    ///
+    /// Default `"lease_revoked_by"`.
+    pub fn lease_revoked_by_attribute_name(mut self, name: impl Into<String>) -> Self {
+        self.attribute_names.lease_revoked_by = name.into();
+        self
+    }
+
+    /// Sets the name of the attribute storing the expiry of each shared holder,
+    /// see [`Client::acquire_shared`].
+    ///
+    /// Default `"lease_readers"`.
+    pub fn lease_readers_attribute_name(mut self, name: impl Into<String>) -> Self {
+        self.attribute_names.lease_readers = name.into();
+        self
+    }
+
+    /// Sets the name of the attribute storing the waiter tickets of a fair queue,
+    /// see [`ClientBuilder::fair_acquire`].
+    ///
+    /// Default `"lease_queue"`.
+    pub fn lease_queue_attribute_name(mut self, name: impl Into<String>) -> Self {
+        self.attribute_names.lease_queue = name.into();
+        self
+    }
+
+    /// Sets the owner identity stored with each lease, so operators can trace a lease
+    /// to the process holding it.
+    ///
+    /// Default `"{hostname}:{pid}:{client-uuid}"`, where the uuid is unique for each
+    /// built client.
+    pub fn owner(mut self, owner: impl Into<String>) -> Self {
+        self.owner = Some(owner.into());
+        self
+    }
+
+    /// Adds an attribute to store with each lease, e.g. a job id or deployment version.
+    ///
+    /// Attributes are written when a lease is acquired and preserved when it is extended.
+    /// The `name` must not clash with other lease table attributes, e.g. `lease_expiry`,
+    /// otherwise building the client fails with [`Error::InvalidOptions`].
+    pub fn lease_attribute(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.lease_attributes.insert(name.into(), value.into());
+        self
+    }
+
+    /// Configures use of a table with a composite primary key, i.e. a partition (hash) key
+    /// & a string sort (range) key named `sort_key_attribute_name`.
+    ///
+    /// Lease keys are mapped to partition & sort key values using `split`.
+    /// The partition key attribute name is set with [`ClientBuilder::key_attribute_name`].
+    ///
+    /// By default the table has only a partition key.
+    ///
+    /// # Example
+    /// ```
+    /// // single-table design with leases under the "lease" partition
+    /// let builder = dynamodb_lease::Client::builder()
+    ///     .table_name("app")
+    ///     .key_attribute_name("pk")
+    ///     .composite_key("sk", dynamodb_lease::KeySplit::FixedPartition("lease".into()));
+    /// ```
+    pub fn composite_key(
+        mut self,
+        sort_key_attribute_name: impl Into<String>,
+        split: KeySplit,
+    ) -> Self {
+        self.sort_key = Some(SortKey {
+            attribute_name: sort_key_attribute_name.into(),
+            split,
+        });
+        self
+    }
+
     /// Sets the time to live for each lease (or lease extension) in seconds.
     /// **Must be at least 2**.
     ///
@@ -78,6 +223,21 @@ impl ClientBuilder {
         self
     }
 
+    /// Sets whether leases are automatically extended in the background every
+    /// [`ClientBuilder::extend_every`].
+    ///
+    /// When disabled leases must be explicitly extended with [`crate::Lease::extend`],
+    /// otherwise they expire after the ttl & become lost. E.g. as a dead man's switch, so
+    /// a lease only stays alive while a job makes progress.
+    ///
+    /// [`Client::leader_election`] leases are always automatically extended.
+    ///
+    /// Default `true`.
+    pub fn auto_extend(mut self, auto_extend: bool) -> Self {
+        self.auto_extend = auto_extend;
+        self
+    }
+
     /// Sets how long [`Client::acquire`] waits between attempts to acquire a lease.
     ///
     /// Default `1s`.
@@ -86,40 +246,206 @@ impl ClientBuilder {
         self
     }
 
+    /// Sets how [`Client::acquire`] & [`Client::acquire_timeout`] wait between attempts
+    /// while a lease is held elsewhere.
+    ///
+    /// Default [`AcquireStrategy::Poll`].
+    pub fn acquire_strategy(mut self, strategy: AcquireStrategy) -> Self {
+        self.acquire_strategy = strategy;
+        self
+    }
+
+    /// Sets whether [`Client::acquire`] & [`Client::acquire_timeout`] wait in a fair
+    /// first-in-first-out queue of each key, so contended leases are granted to the
+    /// longest waiting process instead of whichever happens to poll first.
+    ///
+    /// Each waiter puts a ticket into a queue item of the key, `"~lease/queue/{key}"`, refreshed
+    /// every [`ClientBuilder::acquire_cooldown`], or more often if necessary to refresh it
+    /// within half of `lease_ttl_seconds`. Only the waiter with the oldest live ticket
+    /// attempts to acquire the lease. Tickets expire after `lease_ttl_seconds` if not
+    /// refreshed, e.g. if the waiter crashed.
+    ///
+    /// All clients acquiring the same keys should use fair mode, other acquisitions,
+    /// including [`Client::try_acquire`], do not wait in the queue.
+    ///
+    /// Default `false`.
+    pub fn fair_acquire(mut self, fair: bool) -> Self {
+        self.fair_acquire = fair;
+        self
+    }
+
+    /// Sets how background lease extensions retry transient failures, like throttling,
+    /// 5xx responses or timeouts.
+    ///
+    /// Retries never continue past the lease expiry, at which point the lease is considered
+    /// lost. A failed conditional check, i.e. the lease was deleted or replaced, is never retried.
+    ///
+    /// If all attempts fail the lease will be extended again after the shorter of
+    /// [`ClientBuilder::extend_every`] & half the remaining time until expiry.
+    ///
+    /// Default [`RetryPolicy::default`].
+    pub fn extend_retry(mut self, policy: RetryPolicy) -> Self {
+        self.extend_retry = policy;
+        self
+    }
+
+    /// Sets how a dropped [`crate::Lease`] retries transient failures releasing itself.
+    ///
+    /// Retries never continue past the lease expiry, after which the lease may be acquired
+    /// elsewhere anyway. Dropped leases that fail to release are counted in
+    /// [`crate::Metrics::leaked_leases`].
+    ///
+    /// Default [`RetryPolicy::default`].
+    pub fn release_retry(mut self, policy: RetryPolicy) -> Self {
+        self.release_retry = policy;
+        self
+    }
+
+    /// Sets the estimated clock skew, see [`Client::clock_skew`], above which a warning
+    /// is logged with _tracing_.
+    ///
+    /// Default `2s`.
+    pub fn clock_skew_warn_threshold(mut self, threshold: Duration) -> Self {
+        self.clock_skew_warn_threshold = threshold;
+        self
+    }
+
+    /// Sets whether to correct the local clock by the estimated clock skew,
+    /// see [`Client::clock_skew`], when computing lease expiry & the current time
+    /// used to check if leases have expired.
+    ///
+    /// This makes leases expire according to dynamodb's clock rather than the local clock.
+    /// All clients using the same table should use the same setting.
+    ///
+    /// Note: [`crate::LeaseInfo::is_expired`] & [`crate::ListOptions::hide_expired`]
+    /// always use the local clock.
+    ///
+    /// Default `false`.
+    pub fn correct_clock_skew(mut self, correct: bool) -> Self {
+        self.correct_clock_skew = correct;
+        self
+    }
+
+    /// Sets the options used to create the lease table in [`ClientBuilder::build_and_ensure_table`].
+    ///
+    /// Default [`TableOptions::default`], i.e. on-demand billing.
+    pub fn table_options(mut self, options: TableOptions) -> Self {
+        self.table_options = options;
+        self
+    }
+
     /// Builds a [`Client`].
     /// Does not check if the table exists or has the correct schema, see [`ClientBuilder::build_and_check_db`].
     ///
-    /// # Panics
-    /// Panics if `extend_period` is not less than `lease_ttl_seconds`.
-    pub fn build(self, dynamodb_client: aws_sdk_dynamodb::Client) -> Client {
+    /// # Errors
+    /// Returns [`Error::InvalidOptions`] if `extend_period` is not less than `lease_ttl_seconds`,
+    /// or a [`ClientBuilder::lease_attribute`] name clashes with a lease table attribute.
+    pub fn build(self, dynamodb_client: aws_sdk_dynamodb::Client) -> Result<Client, Error> {
+        // estimate clock skew from each response
+        let skew = Arc::new(SkewEstimate::new(self.clock_skew_warn_threshold));
+        let config = dynamodb_client
+            .config()
+            .to_builder()
+            .interceptor(SkewInterceptor(Arc::clone(&skew)))
+            .build();
+        let dynamodb_client = aws_sdk_dynamodb::Client::from_conf(config);
+
+        let store = DynamoDbStore::new(dynamodb_client, self.table_name.clone())
+            .with_attribute_names(self.attribute_names.clone())
+            .with_sort_key(self.sort_key.clone());
+        self.build_client(Arc::new(store), Some(skew))
+    }
+
+    /// Builds a [`Client`] using a custom [`LeaseStore`], e.g. an in-memory store for tests.
+    ///
+    /// Note: [`ClientBuilder::table_name`], attribute names & composite key config are not
+    /// used by custom stores.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidOptions`] like [`ClientBuilder::build`].
+    pub fn build_with_store(self, store: impl LeaseStore) -> Result<Client, Error> {
+        self.build_client(Arc::new(store), None)
+    }
+
+    fn build_client(
+        self,
+        store: Arc<dyn LeaseStore>,
+        skew: Option<Arc<SkewEstimate>>,
+    ) -> Result<Client, Error> {
         let extend_period = self
             .extend_period
             .unwrap_or_else(|| Duration::from_secs_f64(self.lease_ttl_seconds as f64 / 2.0));
-        assert!(
-            extend_period < Duration::from_secs(self.lease_ttl_seconds as _),
-            "extend_period must be less than ttl"
-        );
+        if extend_period >= Duration::from_secs(self.lease_ttl_seconds as _) {
+            return Err(Error::InvalidOptions(
+                "extend_period must be less than ttl".into(),
+            ));
+        }
+        let sort_key = self.sort_key.as_ref().map(|s| s.attribute_name.as_str());
+        let reserved = self.attribute_names.all().chain(sort_key);
+        if let Some(name) = reserved
+            .clone()
+            .find(|name| self.lease_attributes.contains_key(*name))
+        {
+            return Err(Error::InvalidOptions(format!(
+                "lease_attribute {name:?} clashes with a lease table attribute"
+            )));
+        }
 
-        Client {
-            table_name: Arc::new(self.table_name),
-            client: dynamodb_client,
+        Ok(Client {
+            store,
             lease_ttl_seconds: self.lease_ttl_seconds,
             extend_period,
+            auto_extend: self.auto_extend,
             acquire_cooldown: self.acquire_cooldown,
+            fair_acquire: self.fair_acquire,
+            acquire_strategy: self.acquire_strategy,
+            extend_retry: self.extend_retry,
+            release_retry: self.release_retry,
             local_locks: <_>::default(),
-        }
+            counters: <_>::default(),
+            owner: self.owner.unwrap_or_else(default_owner).into(),
+            lease_attributes: Arc::new(self.lease_attributes),
+            clock: Clock {
+                skew,
+                correct: self.correct_clock_skew,
+            },
+        })
     }
 
     /// Builds a [`Client`] and checks the dynamodb table is active with the correct schema.
     ///
-    /// # Panics
-    /// Panics if `extend_period` is not less than `lease_ttl_seconds`.
+    /// # Errors
+    /// Returns [`Error::InvalidOptions`] like [`ClientBuilder::build`].
     pub async fn build_and_check_db(
         self,
         dynamodb_client: aws_sdk_dynamodb::Client,
-    ) -> anyhow::Result<Client> {
-        let client = self.build(dynamodb_client);
+    ) -> Result<Client, Error> {
+        let client = self.build(dynamodb_client)?;
         client.check_schema().await?;
         Ok(client)
     }
+
+    /// Builds a [`Client`], creating the dynamodb table if it doesn't exist & checking it
+    /// is active with the correct schema. See [`Client::create_table`].
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidOptions`] like [`ClientBuilder::build`].
+    pub async fn build_and_ensure_table(
+        mut self,
+        dynamodb_client: aws_sdk_dynamodb::Client,
+    ) -> Result<Client, Error> {
+        let table_options = std::mem::take(&mut self.table_options);
+        let client = self.build(dynamodb_client)?;
+        client.create_table(&table_options).await?;
+        client.check_schema().await?;
+        Ok(client)
+    }
+}
+
+/// Returns `"{hostname}:{pid}:{uuid}"`.
+fn default_owner() -> String {
+    let hostname = hostname::get()
+        .map(|h| h.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "unknown".into());
+    format!("{hostname}:{}:{}", process::id(), Uuid::new_v4())
 }
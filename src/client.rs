@@ -1,42 +1,48 @@
-use crate::{ClientBuilder, Lease, local::LocalLocks};
-use anyhow::{Context, bail, ensure};
-use aws_sdk_dynamodb::{
-    error::SdkError,
-    operation::{
-        delete_item::{DeleteItemError, DeleteItemOutput},
-        put_item::PutItemError,
-        update_item::UpdateItemError,
-    },
-    types::{AttributeValue, KeyType, ScalarAttributeType},
+use crate::{
+    AcquireOptions, AcquireStrategy, ClientBuilder, Error, HandoffToken, LeaderElection, Lease,
+    LeaseIdentity, LeaseInfo, LeaseStore, ListOptions, Metrics, MultiLease, NewLease, Permit,
+    PutLease, RetryPolicy, TableOptions,
+    clock::Clock,
+    lease::{self, LeaseKind, LeaseVersion},
+    local::LocalLocks,
+    metrics::Counters,
+    permit::slot_key,
+    queue::Ticket,
 };
-use aws_smithy_runtime_api::client::orchestrator;
+use futures_core::Stream;
 use std::{
     cmp::min,
+    collections::BTreeMap,
     sync::Arc,
     time::{Duration, Instant},
 };
-use time::OffsetDateTime;
-use tracing::instrument;
+use tokio::sync::OwnedMutexGuard;
+use tracing::{debug, instrument};
 use uuid::Uuid;
 
-const KEY_FIELD: &str = "key";
-const LEASE_EXPIRY_FIELD: &str = "lease_expiry";
-const LEASE_VERSION_FIELD: &str = "lease_version";
-
 /// Client for acquiring [`Lease`]s.
 ///
-/// Communicates with dynamodb to acquire, extend and delete distributed leases.
+/// Communicates with dynamodb, or another [`LeaseStore`], to acquire, extend and delete
+/// distributed leases.
 ///
 /// Local mutex locks are also used to eliminate db contention for usage within
 /// a single `Client` instance or clone.
 #[derive(Debug, Clone)]
 pub struct Client {
-    pub(crate) client: aws_sdk_dynamodb::Client,
-    pub(crate) table_name: Arc<String>,
+    pub(crate) store: Arc<dyn LeaseStore>,
     pub(crate) lease_ttl_seconds: u32,
     pub(crate) extend_period: Duration,
+    pub(crate) auto_extend: bool,
     pub(crate) acquire_cooldown: Duration,
+    pub(crate) fair_acquire: bool,
+    pub(crate) acquire_strategy: AcquireStrategy,
+    pub(crate) extend_retry: RetryPolicy,
+    pub(crate) release_retry: RetryPolicy,
     pub(crate) local_locks: LocalLocks,
+    pub(crate) counters: Arc<Counters>,
+    pub(crate) owner: Arc<str>,
+    pub(crate) lease_attributes: Arc<BTreeMap<String, String>>,
+    pub(crate) clock: Clock,
 }
 
 impl Client {
@@ -45,6 +51,137 @@ impl Client {
         <_>::default()
     }
 
+    /// Creates the lease table, if it doesn't already exist, with the correct schema &
+    /// time to live enabled. Waits for the table to become active.
+    ///
+    /// This is idempotent, so may be called on each startup. For existing tables
+    /// time to live & point-in-time-recovery (if configured) are enabled if necessary.
+    ///
+    /// See also [`ClientBuilder::build_and_ensure_table`].
+    ///
+    /// # Example
+    /// ```
+    /// # async fn foo(client: dynamodb_lease::Client) -> Result<(), dynamodb_lease::Error> {
+    /// client
+    ///     .create_table(&dynamodb_lease::TableOptions::default().point_in_time_recovery(true))
+    ///     .await?;
+    /// # Ok(()) }
+    /// ```
+    pub async fn create_table(&self, options: &TableOptions) -> Result<(), Error> {
+        self.store.create_table(options).await
+    }
+
+    /// Returns the owner identity stored with each lease acquired by this client,
+    /// see [`ClientBuilder::owner`].
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    /// Returns the estimated skew of the local clock vs dynamodb, i.e. dynamodb time minus
+    /// local time, measured from the `Date` header of each dynamodb response.
+    ///
+    /// Leases expire according to the local clocks of the clients that acquire & extend them,
+    /// so large skew between clients breaks lease exclusivity, see
+    /// [`ClientBuilder::correct_clock_skew`] & [`ClientBuilder::clock_skew_warn_threshold`].
+    ///
+    /// Returns `None` until the first response or if using a custom [`LeaseStore`].
+    /// Note: `Date` headers have second precision, so the estimate is a moving average.
+    pub fn clock_skew(&self) -> Option<time::Duration> {
+        self.clock.skew.as_ref()?.get()
+    }
+
+    /// Returns a snapshot of this client's counters, shared with its clones.
+    pub fn metrics(&self) -> Metrics {
+        self.counters.snapshot()
+    }
+
+    /// Returns the current lease for the given `key`, if any, without acquiring it.
+    ///
+    /// Uses a strongly consistent read. Leases that have expired but are not yet deleted
+    /// by time to live are also returned, see [`LeaseInfo::is_expired`].
+    ///
+    /// # Example
+    /// ```
+    /// # async fn foo(client: dynamodb_lease::Client) -> Result<(), dynamodb_lease::Error> {
+    /// if let Some(info) = client.describe("important-job-123").await?
+    ///     && !info.is_expired()
+    /// {
+    ///     println!("held by {:?} until {}", info.owner, info.expires_at());
+    /// }
+    /// # Ok(()) }
+    /// ```
+    #[instrument(skip_all)]
+    pub async fn describe(&self, key: &str) -> Result<Option<LeaseInfo>, Error> {
+        self.store.get_lease(key).await
+    }
+
+    /// Forcibly releases the lease for the given `key` regardless of which client holds it,
+    /// e.g. to recover from a wedged holder that is still extending its lease.
+    ///
+    /// If `expected_version` is set the lease is only released if it still has this
+    /// [`LeaseInfo::lease_v`], e.g. as observed by [`Client::describe`]. This avoids
+    /// releasing a lease freshly acquired by another holder. Note: the version changes
+    /// each time the lease is extended.
+    ///
+    /// Rather than being deleted the lease is marked expired & revoked, recording this
+    /// client's [`Client::owner`], see [`LeaseInfo::revoked_by`]. So the key may be
+    /// acquired immediately & the evicted holder will observe [`crate::LostReason::Revoked`]
+    /// on its next extension attempt.
+    ///
+    /// # Errors
+    /// Returns [`Error::LeaseLost`] if no lease exists, or it has a different version.
+    ///
+    /// # Example
+    /// ```
+    /// # async fn foo(client: dynamodb_lease::Client) -> Result<(), dynamodb_lease::Error> {
+    /// if let Some(info) = client.describe("important-job-123").await? {
+    ///     client
+    ///         .force_release("important-job-123", Some(info.lease_v))
+    ///         .await?;
+    /// }
+    /// # Ok(()) }
+    /// ```
+    #[instrument(skip_all)]
+    pub async fn force_release(
+        &self,
+        key: &str,
+        expected_version: Option<Uuid>,
+    ) -> Result<(), Error> {
+        let expired = self.clock.now().unix_timestamp() - 1;
+        self.store
+            .revoke_lease(key, expected_version, &self.owner, expired)
+            .await
+    }
+
+    /// Returns a stream of all current leases matching `options`. Pages of leases are
+    /// fetched from the db as the stream is consumed.
+    ///
+    /// Uses strongly consistent _Scan_ requests, or _Query_ requests for a
+    /// [`crate::KeySplit::FixedPartition`] composite key, so may be expensive for large tables.
+    ///
+    /// # Errors
+    /// Yields [`Error::Unsupported`] for a [`crate::KeySplit::Function`] composite key,
+    /// as lease keys cannot be derived from items.
+    ///
+    /// # Example
+    /// ```
+    /// # async fn foo(client: dynamodb_lease::Client) -> Result<(), dynamodb_lease::Error> {
+    /// use futures_util::TryStreamExt;
+    ///
+    /// let options = dynamodb_lease::ListOptions::default().hide_expired(true);
+    /// let mut leases = std::pin::pin!(client.list_leases(&options));
+    /// while let Some(lease) = leases.try_next().await? {
+    ///     println!("{} held by {:?}", lease.key, lease.owner);
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn list_leases(
+        &self,
+        options: &ListOptions,
+    ) -> impl Stream<Item = Result<LeaseInfo, Error>> + Send + 'static {
+        self.store.list_leases(options)
+    }
+
     /// Tries to acquire a new [`Lease`] for the given `key`.
     ///
     /// If this lease has already been acquired elsewhere `Ok(None)` is returned.
@@ -53,16 +190,17 @@ impl Client {
     ///
     /// Does not wait to acquire a lease, to do so see [`Client::acquire`].
     #[instrument(skip_all)]
-    pub async fn try_acquire(&self, key: impl Into<String>) -> anyhow::Result<Option<Lease>> {
+    pub async fn try_acquire(&self, key: impl Into<String>) -> Result<Option<Lease>, Error> {
         let key = key.into();
         let local_guard = match self.local_locks.try_lock(key.clone()) {
             Ok(g) => g,
             Err(_) => return Ok(None),
         };
 
-        match self.put_lease(key).await {
-            Ok(Some(lease)) => Ok(Some(lease.with_local_guard(local_guard))),
-            x => x,
+        match self.put_lease(key, LeaseKind::Exclusive).await {
+            Ok(Ok(lease)) => Ok(Some(lease.with_local_guard(local_guard))),
+            Ok(Err(_)) => Ok(None),
+            Err(err) => Err(err),
         }
     }
 
@@ -74,16 +212,29 @@ impl Client {
     /// lease expires or is released.
     ///
     /// To try to acquire without waiting see [`Client::try_acquire`].
+    ///
+    /// With [`ClientBuilder::fair_acquire`] waits in a first-in-first-out queue of the key.
     #[instrument(skip_all)]
-    pub async fn acquire(&self, key: impl Into<String>) -> anyhow::Result<Lease> {
+    pub async fn acquire(&self, key: impl Into<String>) -> Result<Lease, Error> {
         let key = key.into();
         let local_guard = self.local_locks.lock(key.clone()).await;
+        let ticket = self.fair_acquire.then(|| Ticket::new(self.clone(), &key));
 
         loop {
-            if let Some(lease) = self.put_lease(key.clone()).await? {
-                return Ok(lease.with_local_guard(local_guard));
-            }
-            tokio::time::sleep(self.acquire_cooldown).await;
+            let holder = match self.put_lease_in_turn(&key, ticket.as_ref()).await? {
+                Ok(lease) => return Ok(lease.with_local_guard(local_guard)),
+                Err(holder) => holder,
+            };
+            let cooldown = self.acquire_strategy.cooldown(
+                self.acquire_cooldown,
+                holder.as_ref(),
+                self.clock.now(),
+            );
+            // refresh the ticket before it expires, keeping this waiter's place in the queue
+            let cooldown = ticket
+                .as_ref()
+                .map_or(cooldown, |t| min(cooldown, t.refresh_period()));
+            tokio::time::sleep(cooldown).await;
         }
     }
 
@@ -95,81 +246,589 @@ impl Client {
     /// `max_wait` for that lease expires or is released.
     ///
     /// To try to acquire without waiting see [`Client::try_acquire`].
+    ///
+    /// With [`ClientBuilder::fair_acquire`] waits in a first-in-first-out queue of the key.
+    ///
+    /// # Errors
+    /// Returns [`Error::Timeout`] if the lease could not be acquired within `max_wait`.
+    /// With [`AcquireStrategy::ExpiryAware`] this is returned early if the current holder's
+    /// lease will not expire within `max_wait`.
     #[instrument(skip_all)]
     pub async fn acquire_timeout(
         &self,
         key: impl Into<String>,
         max_wait: Duration,
-    ) -> anyhow::Result<Lease> {
+    ) -> Result<Lease, Error> {
         let start = Instant::now();
         let key = key.into();
 
         let local_guard = tokio::time::timeout(max_wait, self.local_locks.lock(key.clone()))
             .await
-            .context("Could not acquire within {max_wait:?}")?;
+            .map_err(|_| Error::Timeout(max_wait))?;
+        let ticket = self.fair_acquire.then(|| Ticket::new(self.clone(), &key));
+
+        loop {
+            let holder = match self.put_lease_in_turn(&key, ticket.as_ref()).await? {
+                Ok(lease) => return Ok(lease.with_local_guard(local_guard)),
+                Err(holder) => holder,
+            };
+            let elapsed = start.elapsed();
+            if elapsed > max_wait {
+                return Err(Error::Timeout(max_wait));
+            }
+            let remaining_max_wait = max_wait - elapsed;
+            if self
+                .acquire_strategy
+                .outlives(holder.as_ref(), remaining_max_wait, self.clock.now())
+            {
+                debug!(key, ?holder, "lease holder outlives max wait");
+                return Err(Error::Timeout(max_wait));
+            }
+            let cooldown = self.acquire_strategy.cooldown(
+                self.acquire_cooldown,
+                holder.as_ref(),
+                self.clock.now(),
+            );
+            // refresh the ticket before it expires, keeping this waiter's place in the queue
+            let cooldown = ticket
+                .as_ref()
+                .map_or(cooldown, |t| min(cooldown, t.refresh_period()));
+            tokio::time::sleep(min(cooldown, remaining_max_wait)).await;
+        }
+    }
 
+    /// Tries to acquire a new [`Lease`] for the given `key`, like [`Client::try_acquire`],
+    /// overriding client config with `options`.
+    ///
+    /// [`AcquireOptions::acquire_cooldown`] & [`AcquireOptions::timeout`] are not used
+    /// as this does not wait.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidOptions`] if `options` are invalid, see [`Client::acquire_with`].
+    #[instrument(skip_all)]
+    pub async fn try_acquire_with(
+        &self,
+        key: impl Into<String>,
+        options: &AcquireOptions,
+    ) -> Result<Option<Lease>, Error> {
+        self.with_options(options)?.try_acquire(key).await
+    }
+
+    /// Acquires a new [`Lease`] for the given `key`, like [`Client::acquire`] or with
+    /// [`AcquireOptions::timeout`] like [`Client::acquire_timeout`], overriding client
+    /// config with `options`.
+    ///
+    /// The returned lease is extended according to the options, e.g. with a different ttl
+    /// to other leases of this client.
+    ///
+    /// # Example
+    /// ```
+    /// # async fn foo(client: dynamodb_lease::Client) -> Result<(), dynamodb_lease::Error> {
+    /// use dynamodb_lease::AcquireOptions;
+    ///
+    /// let batch_options = AcquireOptions::default().lease_ttl_seconds(10 * 60);
+    /// let lease = client.acquire_with("nightly-batch", &batch_options).await?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidOptions`] if the ttl is less than 2s, or the extend period
+    /// is zero or not less than the ttl. The same rules as [`ClientBuilder`].
+    ///
+    /// Otherwise returns errors like [`Client::acquire_timeout`].
+    #[instrument(skip_all)]
+    pub async fn acquire_with(
+        &self,
+        key: impl Into<String>,
+        options: &AcquireOptions,
+    ) -> Result<Lease, Error> {
+        let client = self.with_options(options)?;
+        match options.timeout {
+            Some(max_wait) => client.acquire_timeout(key, max_wait).await,
+            None => client.acquire(key).await,
+        }
+    }
+
+    /// Returns a clone of this client with config overridden by `options`.
+    fn with_options(&self, options: &AcquireOptions) -> Result<Self, Error> {
+        let lease_ttl_seconds = options.lease_ttl_seconds.unwrap_or(self.lease_ttl_seconds);
+        if lease_ttl_seconds < 2 {
+            return Err(Error::InvalidOptions(
+                "lease_ttl_seconds must be at least 2s, shorter ttls are not supported".into(),
+            ));
+        }
+        let extend_period = match (options.extend_period, options.lease_ttl_seconds) {
+            (Some(period), _) => period,
+            (None, Some(ttl)) => Duration::from_secs_f64(ttl as f64 / 2.0),
+            (None, None) => self.extend_period,
+        };
+        if extend_period.is_zero() {
+            return Err(Error::InvalidOptions(
+                "extend_period must be greater than zero".into(),
+            ));
+        }
+        if extend_period >= Duration::from_secs(lease_ttl_seconds as _) {
+            return Err(Error::InvalidOptions(
+                "extend_period must be less than ttl".into(),
+            ));
+        }
+
+        Ok(Self {
+            lease_ttl_seconds,
+            extend_period,
+            auto_extend: options.auto_extend.unwrap_or(self.auto_extend),
+            acquire_cooldown: options.acquire_cooldown.unwrap_or(self.acquire_cooldown),
+            ..self.clone()
+        })
+    }
+
+    /// Acquires a new exclusive [`Lease`] for the given `key`, the same as [`Client::acquire`].
+    ///
+    /// An exclusive lease cannot be acquired while any other exclusive or shared lease of the
+    /// key is held, so waits until all are released or expire.
+    #[instrument(skip_all)]
+    pub async fn acquire_exclusive(&self, key: impl Into<String>) -> Result<Lease, Error> {
+        self.acquire(key).await
+    }
+
+    /// Tries to acquire a new shared [`Lease`] for the given `key`.
+    ///
+    /// Shared leases of a key may be held concurrently by any number of holders, e.g. readers,
+    /// but not while an exclusive lease, see [`Client::acquire_exclusive`], is held.
+    /// Each shared lease is extended, released & expires independently. Concurrently held
+    /// shared leases have the same [`Lease::fencing_token`].
+    ///
+    /// If an exclusive lease is held elsewhere `Ok(None)` is returned.
+    ///
+    /// Note: While shared leases are continually held, exclusive acquisition will wait.
+    ///
+    /// Does not wait to acquire a lease, to do so see [`Client::acquire_shared`].
+    #[instrument(skip_all)]
+    pub async fn try_acquire_shared(&self, key: impl Into<String>) -> Result<Option<Lease>, Error> {
+        Ok(self.put_lease(key.into(), LeaseKind::Shared).await?.ok())
+    }
+
+    /// Acquires a new shared [`Lease`] for the given `key`. May wait until successful if an
+    /// exclusive lease has been acquired elsewhere.
+    ///
+    /// See [`Client::try_acquire_shared`].
+    ///
+    /// # Example
+    /// ```
+    /// # async fn foo(client: dynamodb_lease::Client) -> Result<(), dynamodb_lease::Error> {
+    /// // many readers may hold the schema lease concurrently
+    /// let reader = client.acquire_shared("schema").await?;
+    /// # drop(reader);
+    ///
+    /// // while a migration requires exclusivity
+    /// let migration = client.acquire_exclusive("schema").await?;
+    /// # Ok(()) }
+    /// ```
+    #[instrument(skip_all)]
+    pub async fn acquire_shared(&self, key: impl Into<String>) -> Result<Lease, Error> {
+        let key = key.into();
+        loop {
+            if let Ok(lease) = self.put_lease(key.clone(), LeaseKind::Shared).await? {
+                return Ok(lease);
+            }
+            tokio::time::sleep(self.acquire_cooldown).await;
+        }
+    }
+
+    /// Tries to acquire one of `max_permits` [`Permit`]s of the distributed counting
+    /// semaphore `name`, i.e. at most `max_permits` holders may concurrently hold a permit.
+    ///
+    /// Each permit is a lease of a slot key `"{name}/permit/{slot}"`, so permits are
+    /// extended, released & expire like any other [`Lease`]. Slots are tried in turn
+    /// from a random slot, so this may make up to `max_permits` db requests.
+    ///
+    /// If all permits are held `Ok(None)` is returned. All holders must use the same
+    /// `max_permits`.
+    ///
+    /// Does not wait to acquire a permit, to do so see [`Client::acquire_permit`].
+    ///
+    /// # Panics
+    /// Panics if `max_permits` is zero.
+    #[instrument(skip_all)]
+    pub async fn try_acquire_permit(
+        &self,
+        name: &str,
+        max_permits: u32,
+    ) -> Result<Option<Permit>, Error> {
+        assert!(max_permits > 0, "max_permits must be greater than zero");
+        // start at a random slot to reduce contention between acquirers
+        let start = fastrand::u32(..max_permits);
+        for offset in 0..max_permits {
+            let slot = (start + offset) % max_permits;
+            if let Some(lease) = self.try_acquire(slot_key(name, slot)).await? {
+                return Ok(Some(Permit::new(lease, slot)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Acquires one of `max_permits` [`Permit`]s of the distributed counting semaphore `name`.
+    /// May wait until successful if all permits are held elsewhere.
+    ///
+    /// See [`Client::try_acquire_permit`].
+    ///
+    /// # Example
+    /// ```
+    /// # async fn foo(client: dynamodb_lease::Client) -> Result<(), dynamodb_lease::Error> {
+    /// // at most 4 concurrent workers calling the rate-limited api
+    /// let permit = client.acquire_permit("rate-limited-api", 4).await?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if `max_permits` is zero.
+    #[instrument(skip_all)]
+    pub async fn acquire_permit(&self, name: &str, max_permits: u32) -> Result<Permit, Error> {
+        loop {
+            if let Some(permit) = self.try_acquire_permit(name, max_permits).await? {
+                return Ok(permit);
+            }
+            tokio::time::sleep(self.acquire_cooldown).await;
+        }
+    }
+
+    /// Acquires one of `max_permits` [`Permit`]s of the distributed counting semaphore `name`.
+    /// May wait until successful if all permits are held elsewhere up to a max of `max_wait`.
+    ///
+    /// See [`Client::try_acquire_permit`].
+    ///
+    /// # Errors
+    /// Returns [`Error::Timeout`] if a permit could not be acquired within `max_wait`.
+    ///
+    /// # Panics
+    /// Panics if `max_permits` is zero.
+    #[instrument(skip_all)]
+    pub async fn acquire_permit_timeout(
+        &self,
+        name: &str,
+        max_permits: u32,
+        max_wait: Duration,
+    ) -> Result<Permit, Error> {
+        let start = Instant::now();
         loop {
-            if let Some(lease) = self.put_lease(key.clone()).await? {
-                return Ok(lease.with_local_guard(local_guard));
+            if let Some(permit) = self.try_acquire_permit(name, max_permits).await? {
+                return Ok(permit);
             }
             let elapsed = start.elapsed();
             if elapsed > max_wait {
-                bail!("Could not acquire within {max_wait:?}");
+                return Err(Error::Timeout(max_wait));
             }
             let remaining_max_wait = max_wait - elapsed;
             tokio::time::sleep(min(self.acquire_cooldown, remaining_max_wait)).await;
         }
     }
 
-    /// Put a new lease into the db.
-    async fn put_lease(&self, key: String) -> anyhow::Result<Option<Lease>> {
-        let now_ts = OffsetDateTime::now_utc().unix_timestamp();
-        let expiry_timestamp = now_ts + i64::from(self.lease_ttl_seconds);
-        let lease_v = Uuid::new_v4();
+    /// Tries to atomically acquire new [`Lease`]s for all the given `keys`, i.e. either all
+    /// leases are acquired or none are.
+    ///
+    /// Leases are put in a single db transaction, so acquiring multiple keys avoids
+    /// partial holds & deadlocks between processes acquiring overlapping keys.
+    /// The returned [`MultiLease`] extends & releases all leases together.
+    ///
+    /// If any lease has already been acquired elsewhere `Ok(None)` is returned.
+    ///
+    /// Does not wait to acquire leases, to do so see [`Client::acquire_all`].
+    ///
+    /// # Panics
+    /// Panics if `keys` is empty. Duplicate keys are ignored.
+    ///
+    /// Note: DynamoDB transactions are limited to 100 items, each key uses 2 items
+    /// so at most 50 keys may be acquired together.
+    #[instrument(skip_all)]
+    pub async fn try_acquire_all(
+        &self,
+        keys: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<Option<MultiLease>, Error> {
+        let keys = sorted_keys(keys);
+        let mut local_guards = Vec::with_capacity(keys.len());
+        for key in &keys {
+            match self.local_locks.try_lock(key.clone()) {
+                Ok(g) => local_guards.push(g),
+                Err(_) => return Ok(None),
+            }
+        }
 
-        let put = self
-            .client
-            .put_item()
-            .table_name(self.table_name.as_str())
-            .item(KEY_FIELD, AttributeValue::S(key.clone()))
-            .item(
-                LEASE_EXPIRY_FIELD,
-                AttributeValue::N(expiry_timestamp.to_string()),
-            )
-            .item(LEASE_VERSION_FIELD, AttributeValue::S(lease_v.to_string()))
-            .condition_expression("attribute_not_exists(#k) OR #le < :now")
-            .expression_attribute_names("#k", KEY_FIELD)
-            .expression_attribute_names("#le", LEASE_EXPIRY_FIELD)
-            .expression_attribute_values(":now", AttributeValue::N(now_ts.to_string()))
-            .send()
-            .await;
-
-        match put {
-            Err(SdkError::ServiceError(se))
-                if matches!(se.err(), PutItemError::ConditionalCheckFailedException(..)) =>
-            {
-                Ok(None)
+        Ok(self
+            .put_leases(keys)
+            .await?
+            .map(|leases| with_local_guards(leases, local_guards)))
+    }
+
+    /// Atomically acquires new [`Lease`]s for all the given `keys`. May wait until successful
+    /// if any lease has already been acquired elsewhere up to a max of `max_wait`.
+    ///
+    /// See [`Client::try_acquire_all`].
+    ///
+    /// # Example
+    /// ```
+    /// # use std::time::Duration;
+    /// # async fn foo(client: dynamodb_lease::Client) -> Result<(), dynamodb_lease::Error> {
+    /// let leases = client
+    ///     .acquire_all(["account:source", "account:destination"], Duration::from_secs(30))
+    ///     .await?;
+    /// // transfer between accounts...
+    /// leases.release().await?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns [`Error::Timeout`] if the leases could not be acquired within `max_wait`.
+    ///
+    /// # Panics
+    /// Panics if `keys` is empty. Duplicate keys are ignored.
+    #[instrument(skip_all)]
+    pub async fn acquire_all(
+        &self,
+        keys: impl IntoIterator<Item = impl Into<String>>,
+        max_wait: Duration,
+    ) -> Result<MultiLease, Error> {
+        let start = Instant::now();
+        let keys = sorted_keys(keys);
+
+        // lock in key order to avoid local deadlocks
+        let local_guards = tokio::time::timeout(max_wait, async {
+            let mut local_guards = Vec::with_capacity(keys.len());
+            for key in &keys {
+                local_guards.push(self.local_locks.lock(key.clone()).await);
+            }
+            local_guards
+        })
+        .await
+        .map_err(|_| Error::Timeout(max_wait))?;
+
+        loop {
+            if let Some(leases) = self.put_leases(keys.clone()).await? {
+                return Ok(with_local_guards(leases, local_guards));
+            }
+            let elapsed = start.elapsed();
+            if elapsed > max_wait {
+                return Err(Error::Timeout(max_wait));
             }
-            Err(err) => Err(err.into()),
-            Ok(_) => Ok(Some(Lease::new(self.clone(), key, lease_v))),
+            let remaining_max_wait = max_wait - elapsed;
+            tokio::time::sleep(min(self.acquire_cooldown, remaining_max_wait)).await;
         }
     }
 
+    /// Adopts a lease handed off by another client or process, see [`Lease::into_handoff_token`].
+    ///
+    /// Succeeds only if the lease still has the token's `lease_version`, rotating it like an
+    /// extension, & sets this client's [`Client::owner`] as the lease owner. The returned
+    /// [`Lease`] keeps the original fencing token & is extended in the background as usual.
+    ///
+    /// # Errors
+    /// Returns [`Error::LeaseLost`] if the lease has since been deleted, replaced or extended
+    /// elsewhere, or [`Error::LeaseRevoked`] if it has been revoked.
+    #[instrument(skip_all)]
+    pub async fn adopt(&self, token: HandoffToken) -> Result<Lease, Error> {
+        let HandoffToken {
+            key,
+            kind,
+            fencing_token,
+            lease_v,
+        } = token;
+        self.resume_lease(key, kind, fencing_token, lease_v).await
+    }
+
+    /// Reclaims a lease held before restarting, using its persisted [`LeaseIdentity`],
+    /// see [`Lease::watch_identity`].
+    ///
+    /// Succeeds only if the lease still has the identity's `lease_version`, i.e. it has not
+    /// expired & been acquired elsewhere, rotating it like an extension. The returned [`Lease`]
+    /// keeps the identity's owner & fencing token & is extended in the background as usual.
+    ///
+    /// Note: If the process stopped after an extension but before persisting the new
+    /// identity the lease cannot be reclaimed, so must be acquired after it expires.
+    ///
+    /// # Errors
+    /// Returns [`Error::LeaseLost`] if the lease has since been deleted, replaced or extended
+    /// with another version, or [`Error::LeaseRevoked`] if it has been revoked.
+    #[instrument(skip_all)]
+    pub async fn reclaim(&self, identity: LeaseIdentity) -> Result<Lease, Error> {
+        let kind = identity.kind();
+        let client = Self {
+            owner: identity.owner.into(),
+            ..self.clone()
+        };
+        client
+            .resume_lease(identity.key, kind, identity.fencing_token, identity.lease_v)
+            .await
+    }
+
+    /// Takes over an active lease with version `lease_v`, setting this client's owner, &
+    /// starts extending it. See [`Client::adopt`] & [`Client::reclaim`].
+    async fn resume_lease(
+        &self,
+        key: String,
+        kind: LeaseKind,
+        fencing_token: u64,
+        lease_v: Uuid,
+    ) -> Result<Lease, Error> {
+        // waiters in this client may hold the local lock, but can't acquire until released
+        let local_guard = match kind {
+            LeaseKind::Exclusive => self.local_locks.try_lock(key.clone()).ok(),
+            LeaseKind::Shared => None,
+        };
+
+        let version = self.adopt_lease(&key, kind, lease_v).await?;
+        let lease = Lease::new(self.clone(), key, kind, fencing_token, version);
+        Ok(match local_guard {
+            Some(guard) => lease.with_local_guard(guard),
+            None => lease,
+        })
+    }
+
+    /// Starts a [`LeaderElection`] of the given `key`, with this process as a candidate
+    /// identified by `candidate_id`.
+    ///
+    /// The leader holds an exclusive [`Lease`] of the key, with `candidate_id` stored as its
+    /// owner so followers can observe who leads. Followers poll to acquire the lease every
+    /// [`ClientBuilder::acquire_cooldown`].
+    ///
+    /// # Example
+    /// ```
+    /// # async fn foo(client: dynamodb_lease::Client) -> Result<(), dynamodb_lease::Error> {
+    /// # async fn do_leader_work() {}
+    /// use dynamodb_lease::Leadership;
+    ///
+    /// let election = client.leader_election("scheduler", "pod-a");
+    /// let mut leadership = election.watch();
+    /// loop {
+    ///     _ = leadership.wait_for(Leadership::is_leader).await;
+    ///     tokio::select! {
+    ///         _ = do_leader_work() => break,
+    ///         _ = leadership.wait_for(|l| !l.is_leader()) => {}
+    ///     }
+    /// }
+    /// election.step_down().await?;
+    /// # Ok(()) }
+    /// ```
+    pub fn leader_election(
+        &self,
+        key: impl Into<String>,
+        candidate_id: impl Into<String>,
+    ) -> LeaderElection {
+        let client = Self {
+            owner: candidate_id.into().into(),
+            auto_extend: true,
+            ..self.clone()
+        };
+        LeaderElection::start(client, key.into())
+    }
+
+    /// Put new leases for multiple `keys` into the store atomically.
+    async fn put_leases(&self, keys: Vec<String>) -> Result<Option<Vec<Lease>>, Error> {
+        let now_ts = self.clock.now().unix_timestamp();
+        let expiry = now_ts + i64::from(self.lease_ttl_seconds);
+
+        let new_leases: Vec<_> = keys
+            .iter()
+            .map(|key| NewLease {
+                key: key.clone(),
+                lease_v: Uuid::new_v4(),
+                expiry,
+                now: now_ts,
+                owner: self.owner.to_string(),
+                attributes: (*self.lease_attributes).clone(),
+            })
+            .collect();
+        let lease_vs: Vec<_> = new_leases.iter().map(|l| l.lease_v).collect();
+
+        let Some(fencing_tokens) = self.store.put_leases(new_leases).await? else {
+            return Ok(None);
+        };
+
+        let leases: Vec<_> = keys
+            .into_iter()
+            .zip(lease_vs)
+            .zip(fencing_tokens)
+            .map(|((key, lease_v), fencing_token)| {
+                let version = LeaseVersion { lease_v, expiry };
+                Lease::new_unextended(
+                    self.clone(),
+                    key,
+                    LeaseKind::Exclusive,
+                    fencing_token,
+                    version,
+                )
+            })
+            .collect();
+        lease::start_periodically_extending(self.clone(), &leases);
+        Ok(Some(leases))
+    }
+
+    /// Put a new exclusive lease into the store, if it is the `ticket`'s turn in the
+    /// fair queue or not using fair mode.
+    async fn put_lease_in_turn(
+        &self,
+        key: &str,
+        ticket: Option<&Ticket>,
+    ) -> Result<Result<Lease, Option<LeaseInfo>>, Error> {
+        if let Some(ticket) = ticket
+            && !ticket.refresh().await?
+        {
+            return Ok(Err(None));
+        }
+        self.put_lease(key.into(), LeaseKind::Exclusive).await
+    }
+
+    /// Put a new lease into the store. If the lease is held elsewhere returns
+    /// `Ok(Err(holder))`, with the current holder if known.
+    async fn put_lease(
+        &self,
+        key: String,
+        kind: LeaseKind,
+    ) -> Result<Result<Lease, Option<LeaseInfo>>, Error> {
+        let now_ts = self.clock.now().unix_timestamp();
+        let expiry = now_ts + i64::from(self.lease_ttl_seconds);
+        let lease_v = Uuid::new_v4();
+
+        let new_lease = NewLease {
+            key: key.clone(),
+            lease_v,
+            expiry,
+            now: now_ts,
+            owner: self.owner.to_string(),
+            attributes: (*self.lease_attributes).clone(),
+        };
+        let fencing_token = match kind {
+            LeaseKind::Exclusive => match self.store.put_lease(new_lease).await? {
+                PutLease::Acquired { fencing_token } => fencing_token,
+                PutLease::Held { holder } => return Ok(Err(holder)),
+            },
+            LeaseKind::Shared => match self.store.put_shared_lease(new_lease).await? {
+                Some(fencing_token) => fencing_token,
+                None => return Ok(Err(None)),
+            },
+        };
+
+        Ok(Ok(Lease::new(
+            self.clone(),
+            key,
+            kind,
+            fencing_token,
+            LeaseVersion { lease_v, expiry },
+        )))
+    }
+
     /// Delete a lease with a given `key` & `lease_v`.
+    ///
+    /// Returns [`Error::LeaseLost`] if the lease has already been deleted or replaced.
     #[instrument(skip_all)]
     pub(crate) async fn delete_lease(
         &self,
-        key: String,
+        key: &str,
+        kind: LeaseKind,
         lease_v: Uuid,
-    ) -> Result<DeleteItemOutput, SdkError<DeleteItemError, orchestrator::HttpResponse>> {
-        self.client
-            .delete_item()
-            .table_name(self.table_name.as_str())
-            .key(KEY_FIELD, AttributeValue::S(key))
-            .condition_expression(format!("{LEASE_VERSION_FIELD}=:lease_v"))
-            .expression_attribute_values(":lease_v", AttributeValue::S(lease_v.to_string()))
-            .send()
-            .await
+    ) -> Result<(), Error> {
+        match kind {
+            LeaseKind::Exclusive => self.store.delete_lease(key, lease_v).await,
+            LeaseKind::Shared => self.store.delete_shared_lease(key, lease_v).await,
+        }
     }
 
     /// Cleanup local lock memory for the given `key` if not in use.
@@ -177,115 +836,126 @@ impl Client {
         self.local_locks.try_remove(key)
     }
 
-    /// Extends an active lease. Returns the new `lease_v` uuid.
+    /// Extends an active lease. Returns the new `lease_v` uuid & expiry.
+    ///
+    /// Returns [`Error::LeaseLost`] if the lease has been deleted or replaced.
     #[instrument(skip_all)]
     pub(crate) async fn extend_lease(
         &self,
-        key: String,
+        key: &str,
+        kind: LeaseKind,
         lease_v: Uuid,
-    ) -> Result<Uuid, SdkError<UpdateItemError, orchestrator::HttpResponse>> {
-        let expiry_timestamp =
-            OffsetDateTime::now_utc().unix_timestamp() + i64::from(self.lease_ttl_seconds);
+    ) -> Result<LeaseVersion, Error> {
+        let now_ts = self.clock.now().unix_timestamp();
+        let expiry = now_ts + i64::from(self.lease_ttl_seconds);
         let new_lease_v = Uuid::new_v4();
 
-        self.client
-            .update_item()
-            .table_name(self.table_name.as_str())
-            .key(KEY_FIELD, AttributeValue::S(key))
-            .update_expression(format!(
-                "SET {LEASE_VERSION_FIELD}=:new_lease_v, {LEASE_EXPIRY_FIELD}=:expiry"
-            ))
-            .condition_expression(format!("{LEASE_VERSION_FIELD}=:lease_v"))
-            .expression_attribute_values(":new_lease_v", AttributeValue::S(new_lease_v.to_string()))
-            .expression_attribute_values(":lease_v", AttributeValue::S(lease_v.to_string()))
-            .expression_attribute_values(":expiry", AttributeValue::N(expiry_timestamp.to_string()))
-            .send()
-            .await?;
-
-        Ok(new_lease_v)
-    }
-
-    /// Checks table is active & has a valid schema.
-    pub(crate) async fn check_schema(&self) -> anyhow::Result<()> {
-        // fetch table & ttl descriptions concurrently
-        let (table_desc, ttl_desc) = tokio::join!(
-            self.client
-                .describe_table()
-                .table_name(self.table_name.as_str())
-                .send(),
-            self.client
-                .describe_time_to_live()
-                .table_name(self.table_name.as_str())
-                .send()
-        );
-
-        let desc = table_desc
-            .with_context(|| format!("Missing table `{}`?", self.table_name))?
-            .table
-            .context("no table description")?;
-
-        // check "key" field is a S hash key
-        let attrs = desc.attribute_definitions.unwrap_or_default();
-        let key_schema = desc.key_schema.unwrap_or_default();
-        ensure!(
-            key_schema.len() == 1,
-            "Unexpected number of keys ({}) in key_schema, expected 1. Got {:?}",
-            key_schema.len(),
-            vec(key_schema.iter().map(|k| k.attribute_name())),
-        );
-        let described_kind = attrs
-            .iter()
-            .find(|attr| attr.attribute_name() == KEY_FIELD)
-            .with_context(|| {
-                format!(
-                    "Missing attribute definition for {KEY_FIELD}, available {:?}",
-                    vec(attrs.iter().map(|a| a.attribute_name()))
-                )
-            })?
-            .attribute_type();
-        ensure!(
-            described_kind == &ScalarAttributeType::S,
-            "Unexpected attribute type `{:?}` for {}, expected `{:?}`",
-            described_kind,
-            KEY_FIELD,
-            ScalarAttributeType::S,
-        );
-
-        let described_key_type = key_schema
+        match kind {
+            LeaseKind::Exclusive => {
+                self.store
+                    .extend_lease(key, lease_v, new_lease_v, expiry)
+                    .await?
+            }
+            LeaseKind::Shared => {
+                self.store
+                    .extend_shared_lease(key, lease_v, new_lease_v, expiry, now_ts)
+                    .await?
+            }
+        }
+
+        Ok(LeaseVersion {
+            lease_v: new_lease_v,
+            expiry,
+        })
+    }
+
+    /// Adopts an active lease with version `lease_v`, see [`Client::resume_lease`].
+    /// Returns the new `lease_v` uuid & expiry.
+    async fn adopt_lease(
+        &self,
+        key: &str,
+        kind: LeaseKind,
+        lease_v: Uuid,
+    ) -> Result<LeaseVersion, Error> {
+        let now_ts = self.clock.now().unix_timestamp();
+        let expiry = now_ts + i64::from(self.lease_ttl_seconds);
+        let new_lease_v = Uuid::new_v4();
+
+        match kind {
+            LeaseKind::Exclusive => {
+                self.store
+                    .adopt_lease(key, lease_v, new_lease_v, expiry, &self.owner)
+                    .await?
+            }
+            // shared holders have no individual owner
+            LeaseKind::Shared => {
+                self.store
+                    .extend_shared_lease(key, lease_v, new_lease_v, expiry, now_ts)
+                    .await?
+            }
+        }
+
+        Ok(LeaseVersion {
+            lease_v: new_lease_v,
+            expiry,
+        })
+    }
+
+    /// Extends multiple active exclusive leases atomically, `leases` are `(key, lease_v)`.
+    /// Returns the new `lease_v` uuid & expiry of each lease, in order.
+    ///
+    /// Returns [`Error::LeaseLost`] if any lease has been deleted or replaced.
+    #[instrument(skip_all)]
+    pub(crate) async fn extend_leases(
+        &self,
+        leases: &[(&str, Uuid)],
+    ) -> Result<Vec<LeaseVersion>, Error> {
+        let expiry = self.clock.now().unix_timestamp() + i64::from(self.lease_ttl_seconds);
+        let extensions: Vec<_> = leases
             .iter()
-            .find(|k| k.attribute_name() == KEY_FIELD)
-            .with_context(|| {
-                format!(
-                    "Missing key schema for {KEY_FIELD}, available {:?}",
-                    vec(key_schema.iter().map(|k| k.attribute_name()))
-                )
-            })?
-            .key_type();
-        ensure!(
-            described_key_type == &KeyType::Hash,
-            "Unexpected key type `{:?}` for {}, expected `{:?}`",
-            described_key_type,
-            KEY_FIELD,
-            KeyType::Hash,
-        );
-
-        // check "lease_expiry" is a ttl field
-        let update_time_to_live_desc = ttl_desc
-            .with_context(|| format!("Missing time_to_live for table `{}`?", self.table_name))?
-            .time_to_live_description
-            .context("no time to live description")?;
-
-        ensure!(
-            update_time_to_live_desc.attribute_name() == Some(LEASE_EXPIRY_FIELD),
-            "time to live for {} is not set",
-            LEASE_EXPIRY_FIELD,
-        );
-
-        Ok(())
+            .map(|&(key, lease_v)| (key, lease_v, Uuid::new_v4()))
+            .collect();
+
+        self.store.extend_leases(&extensions, expiry).await?;
+
+        Ok(extensions
+            .into_iter()
+            .map(|(_, _, lease_v)| LeaseVersion { lease_v, expiry })
+            .collect())
     }
+
+    /// Deletes multiple exclusive leases atomically, `leases` are `(key, lease_v)`.
+    ///
+    /// Returns [`Error::LeaseLost`] if any lease has already been deleted or replaced.
+    #[instrument(skip_all)]
+    pub(crate) async fn delete_leases(&self, leases: &[(&str, Uuid)]) -> Result<(), Error> {
+        self.store.delete_leases(leases).await
+    }
+
+    /// Checks the store is ready to use, e.g. the table is active & has a valid schema.
+    pub(crate) async fn check_schema(&self) -> Result<(), Error> {
+        self.store.check_schema().await
+    }
+}
+
+/// Returns sorted, deduplicated `keys`.
+///
+/// # Panics
+/// Panics if `keys` is empty.
+fn sorted_keys(keys: impl IntoIterator<Item = impl Into<String>>) -> Vec<String> {
+    let mut keys: Vec<String> = keys.into_iter().map(Into::into).collect();
+    assert!(!keys.is_empty(), "keys must not be empty");
+    keys.sort_unstable();
+    keys.dedup();
+    keys
 }
 
-#[inline]
-fn vec<T>(iter: impl Iterator<Item = T>) -> Vec<T> {
-    iter.collect()
+fn with_local_guards(leases: Vec<Lease>, local_guards: Vec<OwnedMutexGuard<()>>) -> MultiLease {
+    MultiLease::new(
+        leases
+            .into_iter()
+            .zip(local_guards)
+            .map(|(lease, guard)| lease.with_local_guard(guard))
+            .collect(),
+    )
 }
@@ -0,0 +1,119 @@
+use aws_sdk_dynamodb::config::{
+    ConfigBag, Intercept, RuntimeComponents,
+    interceptors::BeforeDeserializationInterceptorContextRef,
+};
+use aws_smithy_runtime_api::box_error::BoxError;
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicI64, Ordering},
+    },
+    time::Duration,
+};
+use time::{OffsetDateTime, format_description::well_known::Rfc2822};
+use tracing::{info, warn};
+
+/// Weight of each new sample in the moving average skew estimate, as `1 / SAMPLE_WEIGHT`.
+const SAMPLE_WEIGHT: i64 = 8;
+
+/// Estimate of the local clock's skew vs dynamodb, see [`crate::Client::clock_skew`].
+#[derive(Debug)]
+pub(crate) struct SkewEstimate {
+    /// Server time minus local time in milliseconds, a moving average of samples.
+    millis: AtomicI64,
+    measured: AtomicBool,
+    warn_threshold: Duration,
+    warned: AtomicBool,
+}
+
+impl SkewEstimate {
+    pub(crate) fn new(warn_threshold: Duration) -> Self {
+        Self {
+            millis: AtomicI64::new(0),
+            measured: AtomicBool::new(false),
+            warn_threshold,
+            warned: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the estimated skew, if measured.
+    pub(crate) fn get(&self) -> Option<time::Duration> {
+        self.measured
+            .load(Ordering::Relaxed)
+            .then(|| time::Duration::milliseconds(self.millis.load(Ordering::Relaxed)))
+    }
+
+    /// Adds a sample of the server time, observed at local time `local`.
+    fn sample(&self, server: OffsetDateTime, local: OffsetDateTime) {
+        // server time has second precision, so is on average 500ms behind
+        let sample = (server - local).whole_milliseconds() as i64 + 500;
+        let millis = match self.measured.swap(true, Ordering::Relaxed) {
+            true => {
+                let old = self.millis.load(Ordering::Relaxed);
+                old + (sample - old) / SAMPLE_WEIGHT
+            }
+            false => sample,
+        };
+        self.millis.store(millis, Ordering::Relaxed);
+
+        let skew = Duration::from_millis(millis.unsigned_abs());
+        let exceeded = skew > self.warn_threshold;
+        if exceeded != self.warned.swap(exceeded, Ordering::Relaxed) {
+            match exceeded {
+                true => warn!(
+                    skew_ms = millis,
+                    "local clock skew vs dynamodb exceeds {:?}, leases may expire earlier or \
+                     later than expected",
+                    self.warn_threshold
+                ),
+                false => info!(skew_ms = millis, "local clock skew vs dynamodb recovered"),
+            }
+        }
+    }
+}
+
+/// Current time for computing lease expiry & comparisons, optionally corrected by
+/// the estimated skew, see [`crate::ClientBuilder::correct_clock_skew`].
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Clock {
+    pub(crate) skew: Option<Arc<SkewEstimate>>,
+    pub(crate) correct: bool,
+}
+
+impl Clock {
+    pub(crate) fn now(&self) -> OffsetDateTime {
+        let now = OffsetDateTime::now_utc();
+        match (&self.skew, self.correct) {
+            (Some(skew), true) => now + skew.get().unwrap_or_default(),
+            _ => now,
+        }
+    }
+}
+
+/// Interceptor sampling the `Date` header of each dynamodb response.
+#[derive(Debug)]
+pub(crate) struct SkewInterceptor(pub(crate) Arc<SkewEstimate>);
+
+impl Intercept for SkewInterceptor {
+    fn name(&self) -> &'static str {
+        "DynamoDbLeaseClockSkew"
+    }
+
+    fn read_after_transmit(
+        &self,
+        context: &BeforeDeserializationInterceptorContextRef<'_>,
+        _: &RuntimeComponents,
+        _: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let local = OffsetDateTime::now_utc();
+        let server = context
+            .response()
+            .headers()
+            .get("date")
+            .and_then(|date| OffsetDateTime::parse(date, &Rfc2822).ok());
+        if let Some(server) = server {
+            self.0.sample(server, local);
+        }
+        Ok(())
+    }
+}
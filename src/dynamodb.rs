@@ -0,0 +1,1645 @@
+use crate::{
+    Error, LeaseInfo, LeaseStore, ListOptions, NewLease, PutLease, store::RESERVED_KEY_PREFIX,
+};
+use async_trait::async_trait;
+use aws_sdk_dynamodb::{
+    client::Waiters,
+    error::SdkError,
+    operation::{
+        create_table::CreateTableError,
+        delete_item::DeleteItemError,
+        describe_table::DescribeTableError,
+        transact_write_items::TransactWriteItemsError,
+        update_item::{UpdateItemError, builders::UpdateItemFluentBuilder},
+    },
+    types::{
+        AttributeDefinition, AttributeValue, BillingMode, Delete, KeySchemaElement, KeyType,
+        PointInTimeRecoverySpecification, ProvisionedThroughput, Put, ReturnValue,
+        ReturnValuesOnConditionCheckFailure, ScalarAttributeType, Tag, TimeToLiveSpecification,
+        TimeToLiveStatus, TransactWriteItem, Update,
+    },
+};
+use aws_smithy_runtime_api::client::orchestrator::HttpResponse;
+use futures_core::stream::BoxStream;
+use futures_util::{StreamExt, TryStreamExt, stream};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::Arc,
+    time::Duration,
+};
+use time::OffsetDateTime;
+use tracing::instrument;
+use uuid::Uuid;
+
+const KEY_FIELD: &str = "key";
+const LEASE_EXPIRY_FIELD: &str = "lease_expiry";
+const LEASE_VERSION_FIELD: &str = "lease_version";
+const LEASE_GENERATION_FIELD: &str = "lease_generation";
+const LEASE_OWNER_FIELD: &str = "lease_owner";
+const LEASE_REVOKED_BY_FIELD: &str = "lease_revoked_by";
+const LEASE_READERS_FIELD: &str = "lease_readers";
+const LEASE_QUEUE_FIELD: &str = "lease_queue";
+
+/// Lease table attribute names, see [`crate::ClientBuilder::key_attribute_name`] etc.
+#[derive(Debug, Clone)]
+pub(crate) struct AttributeNames {
+    pub(crate) key: String,
+    pub(crate) lease_expiry: String,
+    pub(crate) lease_version: String,
+    pub(crate) lease_generation: String,
+    pub(crate) lease_owner: String,
+    pub(crate) lease_revoked_by: String,
+    pub(crate) lease_readers: String,
+    pub(crate) lease_queue: String,
+}
+
+impl AttributeNames {
+    /// Returns all attribute names.
+    pub(crate) fn all(&self) -> impl Iterator<Item = &str> + Clone {
+        [
+            &self.key,
+            &self.lease_expiry,
+            &self.lease_version,
+            &self.lease_generation,
+            &self.lease_owner,
+            &self.lease_revoked_by,
+            &self.lease_readers,
+            &self.lease_queue,
+        ]
+        .into_iter()
+        .map(String::as_str)
+    }
+}
+
+impl Default for AttributeNames {
+    fn default() -> Self {
+        Self {
+            key: KEY_FIELD.into(),
+            lease_expiry: LEASE_EXPIRY_FIELD.into(),
+            lease_version: LEASE_VERSION_FIELD.into(),
+            lease_generation: LEASE_GENERATION_FIELD.into(),
+            lease_owner: LEASE_OWNER_FIELD.into(),
+            lease_revoked_by: LEASE_REVOKED_BY_FIELD.into(),
+            lease_readers: LEASE_READERS_FIELD.into(),
+            lease_queue: LEASE_QUEUE_FIELD.into(),
+        }
+    }
+}
+
+/// A dynamodb item.
+type Item = HashMap<String, AttributeValue>;
+
+/// An update of a lease item, for _UpdateItem_ or _TransactWriteItems_ requests.
+struct ItemUpdate {
+    key: Item,
+    update_expression: String,
+    condition_expression: String,
+    names: HashMap<String, String>,
+    values: HashMap<String, AttributeValue>,
+}
+
+impl ItemUpdate {
+    fn into_transact_item(self, table_name: &str) -> TransactWriteItem {
+        let update = Update::builder()
+            .table_name(table_name)
+            .set_key(Some(self.key))
+            .update_expression(self.update_expression)
+            .condition_expression(self.condition_expression)
+            .set_expression_attribute_names(Some(self.names))
+            .set_expression_attribute_values(Some(self.values))
+            .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld)
+            .build()
+            .expect("all fields set");
+        TransactWriteItem::builder().update(update).build()
+    }
+}
+
+/// Why a _TransactWriteItems_ request failed.
+enum TransactFailure {
+    /// A condition check failed for the transact item at `index`, with the existing item if any.
+    ConditionFailed {
+        index: usize,
+        item: Option<Item>,
+    },
+    /// Cancelled due to a conflicting concurrent request.
+    Conflict(Error),
+    Other(Error),
+}
+
+impl From<SdkError<TransactWriteItemsError, HttpResponse>> for TransactFailure {
+    fn from(err: SdkError<TransactWriteItemsError, HttpResponse>) -> Self {
+        let reasons = match err.as_service_error() {
+            Some(TransactWriteItemsError::TransactionCanceledException(e)) => {
+                e.cancellation_reasons()
+            }
+            _ => &[],
+        };
+        if let Some((index, reason)) = reasons
+            .iter()
+            .enumerate()
+            .find(|(_, r)| r.code() == Some("ConditionalCheckFailed"))
+        {
+            let item = reason.item().cloned();
+            return Self::ConditionFailed { index, item };
+        }
+        let conflict = reasons
+            .iter()
+            .any(|r| r.code() == Some("TransactionConflict"));
+        match conflict {
+            true => Self::Conflict(Error::Db {
+                operation: "TransactWriteItems",
+                retryable: true,
+                source: err.into(),
+            }),
+            false => Self::Other(Error::from_sdk("TransactWriteItems", err)),
+        }
+    }
+}
+
+/// Max attempts to set a shared holder's expiry, see [`DynamoDbStore::set_reader`].
+const SET_READER_ATTEMPTS: usize = 3;
+
+/// Max expired shared holders removed in one update, see [`DynamoDbStore::prune_readers`].
+const PRUNE_READERS_MAX: usize = 50;
+
+/// Max attempts to put a fair queue ticket, see [`LeaseStore::put_ticket`].
+const PUT_TICKET_ATTEMPTS: usize = 3;
+
+/// Max attempts to put a lease while its generation is concurrently incremented,
+/// see [`DynamoDbStore::put`]. Exhausting attempts fails with a retryable [`Error::Db`].
+const PUT_LEASE_ATTEMPTS: usize = 3;
+
+/// Max time to wait for a created table to become active.
+const TABLE_ACTIVE_MAX_WAIT: Duration = Duration::from_secs(5 * 60);
+
+/// Options for creating a lease table, see [`crate::Client::create_table`].
+///
+/// # Example
+/// ```
+/// let options = dynamodb_lease::TableOptions::default()
+///     .provisioned(5, 5)
+///     .tag("team", "platform")
+///     .point_in_time_recovery(true);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TableOptions {
+    billing_mode: BillingMode,
+    provisioned_throughput: Option<ProvisionedThroughput>,
+    tags: Vec<Tag>,
+    point_in_time_recovery: bool,
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        Self {
+            billing_mode: BillingMode::PayPerRequest,
+            provisioned_throughput: None,
+            tags: <_>::default(),
+            point_in_time_recovery: false,
+        }
+    }
+}
+
+impl TableOptions {
+    /// Sets the table to on-demand, pay-per-request billing.
+    ///
+    /// This is the default.
+    pub fn on_demand(mut self) -> Self {
+        self.billing_mode = BillingMode::PayPerRequest;
+        self.provisioned_throughput = None;
+        self
+    }
+
+    /// Sets the table to provisioned billing with the given read & write capacity units.
+    pub fn provisioned(mut self, read_capacity_units: i64, write_capacity_units: i64) -> Self {
+        self.billing_mode = BillingMode::Provisioned;
+        self.provisioned_throughput = Some(
+            ProvisionedThroughput::builder()
+                .read_capacity_units(read_capacity_units)
+                .write_capacity_units(write_capacity_units)
+                .build()
+                .expect("all fields set"),
+        );
+        self
+    }
+
+    /// Adds a tag to apply to a newly created table.
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.push(
+            Tag::builder()
+                .key(key)
+                .value(value)
+                .build()
+                .expect("all fields set"),
+        );
+        self
+    }
+
+    /// Sets whether to enable point-in-time-recovery (continuous backups) for the table.
+    /// When `true` this is enabled for existing tables too.
+    ///
+    /// Default `false`.
+    pub fn point_in_time_recovery(mut self, enabled: bool) -> Self {
+        self.point_in_time_recovery = enabled;
+        self
+    }
+}
+
+/// Function splitting a lease key into `(partition, sort)` key values.
+pub type SplitFn = dyn Fn(&str) -> (String, String) + Send + Sync;
+
+/// How lease keys are mapped to the partition & sort key of a table with a composite
+/// primary key, see [`crate::ClientBuilder::composite_key`].
+#[derive(Clone)]
+pub enum KeySplit {
+    /// Use the same partition key value for all leases, with the lease key as the sort key.
+    ///
+    /// This allows querying leases by key prefix.
+    FixedPartition(String),
+    /// Split each lease key into `(partition, sort)` key values.
+    ///
+    /// This allows leases to live in the same partition as the entities they protect.
+    /// The function must be deterministic & map distinct lease keys to distinct values.
+    Function(Arc<SplitFn>),
+}
+
+impl KeySplit {
+    /// Returns a [`KeySplit::Function`].
+    ///
+    /// # Example
+    /// ```
+    /// // "account-123/transfer" -> ("account-123", "lease#transfer")
+    /// let split = dynamodb_lease::KeySplit::function(|key| {
+    ///     let (pk, sk) = key.split_once('/').unwrap_or((key, ""));
+    ///     (pk.into(), format!("lease#{sk}"))
+    /// });
+    /// ```
+    pub fn function(f: impl Fn(&str) -> (String, String) + Send + Sync + 'static) -> Self {
+        Self::Function(Arc::new(f))
+    }
+
+    fn split(&self, key: &str) -> (String, String) {
+        match self {
+            Self::FixedPartition(partition) => (partition.clone(), key.into()),
+            Self::Function(f) => f(key),
+        }
+    }
+}
+
+impl fmt::Debug for KeySplit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FixedPartition(partition) => {
+                f.debug_tuple("FixedPartition").field(partition).finish()
+            }
+            Self::Function(_) => f.write_str("Function"),
+        }
+    }
+}
+
+/// Sort key config for tables with a composite primary key.
+#[derive(Debug, Clone)]
+pub(crate) struct SortKey {
+    pub(crate) attribute_name: String,
+    pub(crate) split: KeySplit,
+}
+
+/// [`LeaseStore`] using a dynamodb table.
+///
+/// The table must have a string hash key `key` & time to live enabled on `lease_expiry`,
+/// see [`LeaseStore::check_schema`]. Attribute names may be configured with
+/// [`crate::ClientBuilder`].
+#[derive(Debug, Clone)]
+pub struct DynamoDbStore {
+    client: aws_sdk_dynamodb::Client,
+    table_name: Arc<String>,
+    names: Arc<AttributeNames>,
+    sort_key: Option<Arc<SortKey>>,
+}
+
+impl DynamoDbStore {
+    /// Returns a new store using the given lease table.
+    pub fn new(client: aws_sdk_dynamodb::Client, table_name: impl Into<String>) -> Self {
+        Self {
+            client,
+            table_name: Arc::new(table_name.into()),
+            names: <_>::default(),
+            sort_key: None,
+        }
+    }
+
+    pub(crate) fn with_attribute_names(mut self, names: AttributeNames) -> Self {
+        self.names = Arc::new(names);
+        self
+    }
+
+    pub(crate) fn with_sort_key(mut self, sort_key: Option<SortKey>) -> Self {
+        self.sort_key = sort_key.map(Arc::new);
+        self
+    }
+
+    /// Returns the name of the attribute storing the lease key, or the part of it mapped
+    /// to the sort key for a composite primary key.
+    fn lease_key_field(&self) -> &str {
+        match &self.sort_key {
+            Some(sort_key) => &sort_key.attribute_name,
+            None => &self.names.key,
+        }
+    }
+
+    /// Returns [`Error::InvalidKey`] if lease `key` maps to an internal item key,
+    /// see [`DynamoDbStore::internal_item_key`].
+    fn check_key(&self, key: &str) -> Result<(), Error> {
+        let reserved = self
+            .item_key(key)
+            .get(self.lease_key_field())
+            .and_then(|v| v.as_s().ok())
+            .is_some_and(|v| v.starts_with(RESERVED_KEY_PREFIX));
+        match reserved {
+            true => Err(Error::InvalidKey(key.into())),
+            false => Ok(()),
+        }
+    }
+
+    /// Returns the primary key attributes of the internal `kind` item of lease `key`,
+    /// e.g. `~lease/generation/{key}`, in the same partition as the lease item.
+    fn internal_item_key(&self, kind: &str, key: &str) -> HashMap<String, AttributeValue> {
+        let mut item_key = self.item_key(key);
+        if let Some(AttributeValue::S(v)) = item_key.get_mut(self.lease_key_field()) {
+            *v = format!("{RESERVED_KEY_PREFIX}{kind}/{v}");
+        }
+        item_key
+    }
+
+    /// Returns the primary key attributes for the given lease `key`.
+    fn item_key(&self, key: &str) -> HashMap<String, AttributeValue> {
+        match &self.sort_key {
+            None => [(self.names.key.clone(), AttributeValue::S(key.into()))].into(),
+            Some(sort_key) => {
+                let (partition, sort) = sort_key.split.split(key);
+                [
+                    (self.names.key.clone(), AttributeValue::S(partition)),
+                    (sort_key.attribute_name.clone(), AttributeValue::S(sort)),
+                ]
+                .into()
+            }
+        }
+    }
+
+    /// Parses a lease item into a [`LeaseInfo`].
+    ///
+    /// String attributes other than the lease table attributes are returned as
+    /// [`LeaseInfo::attributes`].
+    fn lease_info(&self, key: String, item: Item) -> Result<LeaseInfo, Error> {
+        let names = &self.names;
+        let invalid = |field: &str| Error::InvalidSchema(format!("Missing or invalid {field}"));
+        let lease_v = item
+            .get(&names.lease_version)
+            .and_then(|v| v.as_s().ok())
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(|| invalid(&names.lease_version))?;
+        let expiry = item
+            .get(&names.lease_expiry)
+            .and_then(|v| v.as_n().ok())
+            .and_then(|n| n.parse().ok())
+            .ok_or_else(|| invalid(&names.lease_expiry))?;
+        let fencing_token = item
+            .get(&names.lease_generation)
+            .and_then(|v| v.as_n().ok())
+            .and_then(|n| n.parse().ok())
+            .ok_or_else(|| invalid(&names.lease_generation))?;
+
+        let sort_key = self.sort_key.as_ref().map(|k| k.attribute_name.as_str());
+        let mut owner = None;
+        let mut revoked_by = None;
+        let mut attributes = BTreeMap::new();
+        for (name, value) in item {
+            let AttributeValue::S(value) = value else {
+                continue;
+            };
+            if name == names.lease_owner {
+                owner = Some(value);
+            } else if name == names.lease_revoked_by {
+                revoked_by = Some(value);
+            } else if name != names.key
+                && name != names.lease_version
+                && Some(name.as_str()) != sort_key
+            {
+                attributes.insert(name, value);
+            }
+        }
+
+        Ok(LeaseInfo {
+            owner,
+            attributes,
+            revoked_by,
+            ..LeaseInfo::new(key, lease_v, expiry, fencing_token)
+        })
+    }
+
+    /// Puts a new lease, exclusive or shared, if no lease exists or the existing lease
+    /// has expired. Otherwise returns the existing lease.
+    ///
+    /// The lease item & the incremented generation item are written in a single
+    /// _TransactWriteItems_ request, conditional on the generation read beforehand.
+    /// Retried if the generation is concurrently incremented, up to [`PUT_LEASE_ATTEMPTS`].
+    async fn put(&self, lease: NewLease, shared: bool) -> Result<PutLease, Error> {
+        for _ in 0..PUT_LEASE_ATTEMPTS {
+            let generation = self.last_generation(&lease.key).await?;
+            let fencing_token = generation + 1;
+            let items = vec![
+                self.put_item(lease.clone(), shared, fencing_token),
+                self.generation_update(&lease.key, generation)
+                    .into_transact_item(&self.table_name),
+            ];
+
+            let put = self
+                .client
+                .transact_write_items()
+                .set_transact_items(Some(items))
+                .send()
+                .await;
+            match put.map_err(TransactFailure::from) {
+                Err(TransactFailure::ConditionFailed { index: 0, item }) => {
+                    let holder = item.and_then(|item| self.lease_info(lease.key, item).ok());
+                    return Ok(PutLease::Held { holder });
+                }
+                // generation incremented concurrently, retry with the new generation
+                Err(TransactFailure::ConditionFailed { .. } | TransactFailure::Conflict(_)) => {}
+                Err(TransactFailure::Other(err)) => return Err(err),
+                Ok(_) => return Ok(PutLease::Acquired { fencing_token }),
+            }
+        }
+        Err(Error::Db {
+            operation: "TransactWriteItems",
+            retryable: true,
+            source: "lease generation concurrently incremented".into(),
+        })
+    }
+
+    /// Reads the last fencing token issued for lease `key` from its generation item,
+    /// `0` if none has been issued. The generation item has no expiry, so outlives
+    /// deletion of the lease item.
+    async fn last_generation(&self, key: &str) -> Result<u64, Error> {
+        let get = self
+            .client
+            .get_item()
+            .table_name(self.table_name.as_str())
+            .set_key(Some(self.internal_item_key("generation", key)))
+            .consistent_read(true)
+            .send()
+            .await;
+
+        match get.map_err(|err| Error::from_sdk("GetItem", err))?.item {
+            Some(item) => self.fencing_token(Some(&item)),
+            None => Ok(0),
+        }
+    }
+
+    /// Returns the update to increment the generation item of lease `key`, with the
+    /// condition that it is still `generation`.
+    fn generation_update(&self, key: &str, generation: u64) -> ItemUpdate {
+        let mut update = ItemUpdate {
+            key: self.internal_item_key("generation", key),
+            update_expression: "SET #gen = :next".into(),
+            condition_expression: "attribute_not_exists(#gen)".into(),
+            names: [("#gen".into(), self.names.lease_generation.clone())].into(),
+            values: [(
+                ":next".into(),
+                AttributeValue::N((generation + 1).to_string()),
+            )]
+            .into(),
+        };
+        if generation > 0 {
+            update.condition_expression = "#gen = :generation".into();
+            update.values.insert(
+                ":generation".into(),
+                AttributeValue::N(generation.to_string()),
+            );
+        }
+        update
+    }
+
+    /// Returns the transact item to put a new lease, exclusive or shared, with `fencing_token`
+    /// as its `lease_generation`, if no lease exists or the existing lease has expired.
+    ///
+    /// The whole item is replaced, so no attributes of a previous holder remain.
+    fn put_item(&self, lease: NewLease, shared: bool, fencing_token: u64) -> TransactWriteItem {
+        let names = &self.names;
+        let mut item: Item = lease
+            .attributes
+            .into_iter()
+            .map(|(name, value)| (name, AttributeValue::S(value)))
+            .collect();
+        // lease fields & key last, so never overwritten by additional attributes
+        item.extend([
+            (
+                names.lease_expiry.clone(),
+                AttributeValue::N(lease.expiry.to_string()),
+            ),
+            (
+                names.lease_version.clone(),
+                AttributeValue::S(lease.lease_v.to_string()),
+            ),
+            (names.lease_owner.clone(), AttributeValue::S(lease.owner)),
+            (
+                names.lease_generation.clone(),
+                AttributeValue::N(fencing_token.to_string()),
+            ),
+        ]);
+        if shared {
+            let readers = [(
+                lease.lease_v.to_string(),
+                AttributeValue::N(lease.expiry.to_string()),
+            )];
+            item.insert(
+                names.lease_readers.clone(),
+                AttributeValue::M(readers.into()),
+            );
+        }
+        item.extend(self.item_key(&lease.key));
+
+        let put = Put::builder()
+            .table_name(self.table_name.as_str())
+            .set_item(Some(item))
+            .condition_expression("attribute_not_exists(#k) OR #le < :now")
+            .expression_attribute_names("#k", &names.key)
+            .expression_attribute_names("#le", &names.lease_expiry)
+            .expression_attribute_values(":now", AttributeValue::N(lease.now.to_string()))
+            .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld)
+            .build()
+            .expect("all fields set");
+        TransactWriteItem::builder().put(put).build()
+    }
+
+    /// Returns the update to extend an active lease with version `lease_v`.
+    fn extend_update(
+        &self,
+        key: &str,
+        lease_v: Uuid,
+        new_lease_v: Uuid,
+        expiry: i64,
+    ) -> ItemUpdate {
+        ItemUpdate {
+            key: self.item_key(key),
+            update_expression: "SET #lv = :new_lease_v, #le = :expiry".into(),
+            condition_expression: "#lv = :lease_v".into(),
+            names: [
+                ("#lv".into(), self.names.lease_version.clone()),
+                ("#le".into(), self.names.lease_expiry.clone()),
+            ]
+            .into(),
+            values: [
+                (
+                    ":new_lease_v".into(),
+                    AttributeValue::S(new_lease_v.to_string()),
+                ),
+                (":lease_v".into(), AttributeValue::S(lease_v.to_string())),
+                (":expiry".into(), AttributeValue::N(expiry.to_string())),
+            ]
+            .into(),
+        }
+    }
+
+    /// Returns an _UpdateItem_ request for `update`.
+    fn update_item(&self, update: ItemUpdate) -> UpdateItemFluentBuilder {
+        self.client
+            .update_item()
+            .table_name(self.table_name.as_str())
+            .set_key(Some(update.key))
+            .update_expression(update.update_expression)
+            .condition_expression(update.condition_expression)
+            .set_expression_attribute_names(Some(update.names))
+            .set_expression_attribute_values(Some(update.values))
+    }
+
+    /// Sends `update` of a held lease, with a `lease_version` condition, returning
+    /// [`Error::LeaseLost`] or [`Error::LeaseRevoked`] if the condition fails.
+    async fn update_held(&self, update: ItemUpdate) -> Result<(), Error> {
+        let update = self
+            .update_item(update)
+            .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld)
+            .send()
+            .await;
+
+        match update {
+            Err(SdkError::ServiceError(se))
+                if matches!(
+                    se.err(),
+                    UpdateItemError::ConditionalCheckFailedException(..)
+                ) =>
+            {
+                let old_item = match se.err() {
+                    UpdateItemError::ConditionalCheckFailedException(err) => err.item(),
+                    _ => None,
+                };
+                Err(self.lost_error(old_item))
+            }
+            Err(err) => Err(Error::from_sdk("UpdateItem", err)),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Sets the expiry of shared holder `new_lease_v`, replacing shared holder `old_lease_v`
+    /// if set. Returns the updated item. Shared holders that expired before `now` are then
+    /// removed, see [`DynamoDbStore::prune_readers`].
+    ///
+    /// `lease_expiry` is raised if necessary so it is never before any shared holder's
+    /// expiry. Update expressions cannot take a max, so conditions on the current
+    /// `lease_expiry` are used to decide whether to set it, retrying if that changes.
+    ///
+    /// Returns [`Error::LeaseLost`] if the item has no shared holders, or no shared holder
+    /// `old_lease_v` if set.
+    async fn set_reader(
+        &self,
+        key: &str,
+        old_lease_v: Option<Uuid>,
+        new_lease_v: Uuid,
+        expiry: i64,
+        now: i64,
+    ) -> Result<Item, Error> {
+        let names = &self.names;
+        let mut raise_expiry = true;
+        let mut old_item = None;
+        for _ in 0..SET_READER_ATTEMPTS {
+            let mut update_expression = String::from("SET #readers.#new = :expiry");
+            let mut condition = String::from(match old_lease_v {
+                Some(_) => "attribute_exists(#readers.#old)",
+                None => "attribute_exists(#readers)",
+            });
+            match raise_expiry {
+                true => {
+                    update_expression.push_str(", #le = :expiry");
+                    condition.push_str(" AND #le <= :expiry");
+                }
+                false => condition.push_str(" AND #le > :expiry"),
+            }
+            if old_lease_v.is_some() {
+                update_expression.push_str(" REMOVE #readers.#old");
+            }
+
+            let mut update = self
+                .client
+                .update_item()
+                .table_name(self.table_name.as_str())
+                .set_key(Some(self.item_key(key)))
+                .update_expression(update_expression)
+                .condition_expression(condition)
+                .expression_attribute_names("#readers", &names.lease_readers)
+                .expression_attribute_names("#le", &names.lease_expiry)
+                .expression_attribute_names("#new", new_lease_v.to_string())
+                .expression_attribute_values(":expiry", AttributeValue::N(expiry.to_string()))
+                .return_values(ReturnValue::AllNew)
+                .return_values_on_condition_check_failure(
+                    ReturnValuesOnConditionCheckFailure::AllOld,
+                );
+            if let Some(old_lease_v) = old_lease_v {
+                update = update.expression_attribute_names("#old", old_lease_v.to_string());
+            }
+
+            match update.send().await {
+                Err(SdkError::ServiceError(se))
+                    if matches!(
+                        se.err(),
+                        UpdateItemError::ConditionalCheckFailedException(..)
+                    ) =>
+                {
+                    old_item = match se.into_err() {
+                        UpdateItemError::ConditionalCheckFailedException(err) => err.item,
+                        _ => None,
+                    };
+                }
+                Err(err) => return Err(Error::from_sdk("UpdateItem", err)),
+                Ok(out) => {
+                    let item = out.attributes.unwrap_or_default();
+                    self.prune_readers(key, &item, now).await;
+                    return Ok(item);
+                }
+            }
+
+            // check the shared holder condition, otherwise retry with the observed expiry
+            let Some(old) = &old_item else { break };
+            let readers = old.get(&names.lease_readers).and_then(|v| v.as_m().ok());
+            let has_reader = match (readers, old_lease_v) {
+                (Some(readers), Some(old_lease_v)) => {
+                    readers.contains_key(&old_lease_v.to_string())
+                }
+                (readers, _) => readers.is_some(),
+            };
+            if !has_reader {
+                break;
+            }
+            raise_expiry = old
+                .get(&names.lease_expiry)
+                .and_then(|v| v.as_n().ok())
+                .and_then(|n| n.parse::<i64>().ok())
+                .is_none_or(|le| le <= expiry);
+        }
+        Err(self.lost_error(old_item.as_ref()))
+    }
+
+    /// Removes shared holders of `item` that expired before `now`, the client's (possibly
+    /// skew corrected) current time, e.g. crashed holders, so they do not prevent the item
+    /// being deleted when the last active holder is released.
+    ///
+    /// Best effort, each entry is removed with the condition that it is still expired &
+    /// any failure is left to the next join or extension.
+    async fn prune_readers(&self, key: &str, item: &Item, now: i64) {
+        let expired: Vec<_> = item
+            .get(&self.names.lease_readers)
+            .and_then(|v| v.as_m().ok())
+            .into_iter()
+            .flatten()
+            .filter(|(_, expiry)| {
+                expiry
+                    .as_n()
+                    .ok()
+                    .and_then(|n| n.parse::<i64>().ok())
+                    .is_some_and(|expiry| expiry < now)
+            })
+            .map(|(lease_v, _)| lease_v)
+            .take(PRUNE_READERS_MAX)
+            .collect();
+        if expired.is_empty() {
+            return;
+        }
+
+        let (mut remove, mut condition) = (vec![], vec![]);
+        let mut update = self
+            .client
+            .update_item()
+            .table_name(self.table_name.as_str())
+            .set_key(Some(self.item_key(key)))
+            .expression_attribute_names("#readers", &self.names.lease_readers)
+            .expression_attribute_values(":now", AttributeValue::N(now.to_string()));
+        for (idx, lease_v) in expired.into_iter().enumerate() {
+            remove.push(format!("#readers.#r{idx}"));
+            condition.push(format!("#readers.#r{idx} < :now"));
+            update = update.expression_attribute_names(format!("#r{idx}"), lease_v);
+        }
+        let _ = update
+            .update_expression(format!("REMOVE {}", remove.join(", ")))
+            .condition_expression(condition.join(" AND "))
+            .send()
+            .await;
+    }
+
+    /// Parses the fair queue tickets & their expiry of an item.
+    fn queue_tickets(&self, item: &Item) -> BTreeMap<String, i64> {
+        item.get(&self.names.lease_queue)
+            .and_then(|v| v.as_m().ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|(ticket, expiry)| {
+                let expiry = expiry.as_n().ok()?.parse().ok()?;
+                Some((ticket.clone(), expiry))
+            })
+            .collect()
+    }
+
+    /// Parses the `lease_generation` of an item.
+    fn fencing_token(&self, item: Option<&Item>) -> Result<u64, Error> {
+        let names = &self.names;
+        item.and_then(|attrs| attrs.get(&names.lease_generation))
+            .and_then(|v| v.as_n().ok())
+            .and_then(|n| n.parse().ok())
+            .ok_or_else(|| {
+                Error::InvalidSchema(format!("Missing or invalid {}", names.lease_generation))
+            })
+    }
+
+    /// Returns [`Error::LeaseRevoked`] if the item returned by a failed conditional check
+    /// has been revoked, otherwise [`Error::LeaseLost`].
+    fn lost_error(&self, old_item: Option<&Item>) -> Error {
+        let revoked_by = old_item
+            .and_then(|item| item.get(&self.names.lease_revoked_by))
+            .and_then(|v| v.as_s().ok());
+        match revoked_by {
+            Some(revoked_by) => Error::LeaseRevoked {
+                revoked_by: revoked_by.clone(),
+            },
+            None => Error::LeaseLost,
+        }
+    }
+
+    /// Fetches a page of lease items starting after `start`, returning the items & the key
+    /// to start the next page, if any.
+    ///
+    /// Uses _Query_ for a [`KeySplit::FixedPartition`], otherwise _Scan_.
+    async fn list_page(
+        &self,
+        options: &ListOptions,
+        start: Option<Item>,
+    ) -> Result<(Vec<Item>, Option<Item>), Error> {
+        let partition = match self.sort_key.as_deref() {
+            None => None,
+            Some(SortKey {
+                attribute_name,
+                split: KeySplit::FixedPartition(partition),
+            }) => Some((attribute_name, partition)),
+            Some(_) => {
+                return Err(Error::Unsupported(
+                    "listing leases with a KeySplit::Function composite key".into(),
+                ));
+            }
+        };
+
+        let mut names = HashMap::from([("#lv".to_string(), self.names.lease_version.clone())]);
+        let mut values = HashMap::new();
+        let mut filter = vec!["attribute_exists(#lv)"];
+        if options.hide_expired {
+            let now = OffsetDateTime::now_utc().unix_timestamp();
+            names.insert("#le".into(), self.names.lease_expiry.clone());
+            values.insert(":now".into(), AttributeValue::N(now.to_string()));
+            filter.push("#le >= :now");
+        }
+        if let Some(prefix) = &options.prefix {
+            values.insert(":prefix".into(), AttributeValue::S(prefix.clone()));
+        }
+        match partition {
+            Some((sort_attribute_name, partition)) => {
+                names.insert("#k".into(), self.names.key.clone());
+                values.insert(":partition".into(), AttributeValue::S(partition.clone()));
+                let mut key_condition = String::from("#k = :partition");
+                if options.prefix.is_some() {
+                    names.insert("#sk".into(), sort_attribute_name.clone());
+                    key_condition.push_str(" AND begins_with(#sk, :prefix)");
+                }
+                let out = self
+                    .client
+                    .query()
+                    .table_name(self.table_name.as_str())
+                    .key_condition_expression(key_condition)
+                    .filter_expression(filter.join(" AND "))
+                    .set_expression_attribute_names(Some(names))
+                    .set_expression_attribute_values(Some(values))
+                    .set_exclusive_start_key(start)
+                    .consistent_read(true)
+                    .send()
+                    .await
+                    .map_err(|err| Error::from_sdk("Query", err))?;
+                Ok((out.items.unwrap_or_default(), out.last_evaluated_key))
+            }
+            None => {
+                if options.prefix.is_some() {
+                    names.insert("#k".into(), self.names.key.clone());
+                    filter.push("begins_with(#k, :prefix)");
+                }
+                let out = self
+                    .client
+                    .scan()
+                    .table_name(self.table_name.as_str())
+                    .filter_expression(filter.join(" AND "))
+                    .set_expression_attribute_names(Some(names))
+                    .set_expression_attribute_values((!values.is_empty()).then_some(values))
+                    .set_exclusive_start_key(start)
+                    .consistent_read(true)
+                    .send()
+                    .await
+                    .map_err(|err| Error::from_sdk("Scan", err))?;
+                Ok((out.items.unwrap_or_default(), out.last_evaluated_key))
+            }
+        }
+    }
+
+    /// Returns the lease key of a listed item, i.e. the sort key for a
+    /// [`KeySplit::FixedPartition`], otherwise the hash key.
+    fn listed_lease_key(&self, item: &Item) -> Result<String, Error> {
+        let field = self.lease_key_field();
+        item.get(field)
+            .and_then(|v| v.as_s().ok())
+            .cloned()
+            .ok_or_else(|| Error::InvalidSchema(format!("Missing or invalid {field}")))
+    }
+}
+
+#[async_trait]
+impl LeaseStore for DynamoDbStore {
+    /// Creates the table, if it doesn't already exist, waiting for it to become active.
+    /// Then enables time to live on `lease_expiry` & point-in-time-recovery if configured.
+    #[instrument(skip_all)]
+    async fn create_table(&self, options: &TableOptions) -> Result<(), Error> {
+        let table_name = self.table_name.as_str();
+        let names = &self.names;
+        let mut keys = vec![(names.key.as_str(), KeyType::Hash)];
+        if let Some(sort_key) = &self.sort_key {
+            keys.push((sort_key.attribute_name.as_str(), KeyType::Range));
+        }
+
+        let mut create = self
+            .client
+            .create_table()
+            .table_name(table_name)
+            .billing_mode(options.billing_mode.clone())
+            .set_provisioned_throughput(options.provisioned_throughput.clone())
+            .set_tags((!options.tags.is_empty()).then(|| options.tags.clone()));
+        for (field, key_type) in keys {
+            create = create
+                .attribute_definitions(
+                    AttributeDefinition::builder()
+                        .attribute_name(field)
+                        .attribute_type(ScalarAttributeType::S)
+                        .build()
+                        .expect("all fields set"),
+                )
+                .key_schema(
+                    KeySchemaElement::builder()
+                        .attribute_name(field)
+                        .key_type(key_type)
+                        .build()
+                        .expect("all fields set"),
+                );
+        }
+        let create = create.send().await;
+        match create {
+            Err(SdkError::ServiceError(se))
+                if matches!(se.err(), CreateTableError::ResourceInUseException(..)) => {}
+            Err(err) => return Err(Error::from_sdk("CreateTable", err)),
+            Ok(_) => {}
+        }
+
+        self.client
+            .wait_until_table_exists()
+            .table_name(table_name)
+            .wait(TABLE_ACTIVE_MAX_WAIT)
+            .await
+            .map_err(|err| Error::Db {
+                operation: "DescribeTable",
+                retryable: false,
+                source: err.into(),
+            })?;
+
+        let ttl = self
+            .client
+            .describe_time_to_live()
+            .table_name(table_name)
+            .send()
+            .await
+            .map_err(|err| Error::from_sdk("DescribeTimeToLive", err))?;
+        let ttl_enabled = ttl.time_to_live_description().is_some_and(|desc| {
+            desc.attribute_name() == Some(names.lease_expiry.as_str())
+                && matches!(
+                    desc.time_to_live_status(),
+                    Some(TimeToLiveStatus::Enabled | TimeToLiveStatus::Enabling)
+                )
+        });
+        if !ttl_enabled {
+            self.client
+                .update_time_to_live()
+                .table_name(table_name)
+                .time_to_live_specification(
+                    TimeToLiveSpecification::builder()
+                        .enabled(true)
+                        .attribute_name(&names.lease_expiry)
+                        .build()
+                        .expect("all fields set"),
+                )
+                .send()
+                .await
+                .map_err(|err| Error::from_sdk("UpdateTimeToLive", err))?;
+        }
+
+        if options.point_in_time_recovery {
+            self.client
+                .update_continuous_backups()
+                .table_name(table_name)
+                .point_in_time_recovery_specification(
+                    PointInTimeRecoverySpecification::builder()
+                        .point_in_time_recovery_enabled(true)
+                        .build()
+                        .expect("all fields set"),
+                )
+                .send()
+                .await
+                .map_err(|err| Error::from_sdk("UpdateContinuousBackups", err))?;
+        }
+
+        Ok(())
+    }
+
+    /// Put a new lease into the db, incrementing the key's generation.
+    #[instrument(skip_all)]
+    async fn put_lease(&self, lease: NewLease) -> Result<PutLease, Error> {
+        self.check_key(&lease.key)?;
+        self.put(lease, false).await
+    }
+
+    /// Put new leases into the db, incrementing each key's generation, in a single
+    /// _TransactWriteItems_ request, like [`DynamoDbStore::put`].
+    #[instrument(skip_all)]
+    async fn put_leases(&self, leases: Vec<NewLease>) -> Result<Option<Vec<u64>>, Error> {
+        for lease in &leases {
+            self.check_key(&lease.key)?;
+        }
+        for _ in 0..PUT_LEASE_ATTEMPTS {
+            let generations = leases.iter().map(|l| self.last_generation(&l.key));
+            let generations = futures_util::future::try_join_all(generations).await?;
+            // lease item then generation item for each key
+            let items = leases
+                .iter()
+                .zip(&generations)
+                .flat_map(|(lease, generation)| {
+                    [
+                        self.put_item(lease.clone(), false, generation + 1),
+                        self.generation_update(&lease.key, *generation)
+                            .into_transact_item(&self.table_name),
+                    ]
+                })
+                .collect();
+
+            let put = self
+                .client
+                .transact_write_items()
+                .set_transact_items(Some(items))
+                .send()
+                .await;
+            match put.map_err(TransactFailure::from) {
+                Err(TransactFailure::ConditionFailed { index, .. }) if index % 2 == 0 => {
+                    return Ok(None);
+                }
+                // generation incremented concurrently, retry with the new generations
+                Err(TransactFailure::ConditionFailed { .. } | TransactFailure::Conflict(_)) => {}
+                Err(TransactFailure::Other(err)) => return Err(err),
+                Ok(_) => return Ok(Some(generations.iter().map(|g| g + 1).collect())),
+            }
+        }
+        Err(Error::Db {
+            operation: "TransactWriteItems",
+            retryable: true,
+            source: "lease generations concurrently incremented".into(),
+        })
+    }
+
+    /// Extends active leases in a single _TransactWriteItems_ request.
+    #[instrument(skip_all)]
+    async fn extend_leases(&self, leases: &[(&str, Uuid, Uuid)], expiry: i64) -> Result<(), Error> {
+        let items = leases
+            .iter()
+            .map(|(key, lease_v, new_lease_v)| {
+                self.extend_update(key, *lease_v, *new_lease_v, expiry)
+                    .into_transact_item(&self.table_name)
+            })
+            .collect();
+
+        let extend = self
+            .client
+            .transact_write_items()
+            .set_transact_items(Some(items))
+            .send()
+            .await;
+        match extend.map_err(TransactFailure::from) {
+            Err(TransactFailure::ConditionFailed { item, .. }) => {
+                Err(self.lost_error(item.as_ref()))
+            }
+            Err(TransactFailure::Conflict(err) | TransactFailure::Other(err)) => Err(err),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Deletes leases in a single _TransactWriteItems_ request.
+    #[instrument(skip_all)]
+    async fn delete_leases(&self, leases: &[(&str, Uuid)]) -> Result<(), Error> {
+        let items = leases
+            .iter()
+            .map(|(key, lease_v)| {
+                let delete = Delete::builder()
+                    .table_name(self.table_name.as_str())
+                    .set_key(Some(self.item_key(key)))
+                    .condition_expression("#lv = :lease_v")
+                    .expression_attribute_names("#lv", &self.names.lease_version)
+                    .expression_attribute_values(":lease_v", AttributeValue::S(lease_v.to_string()))
+                    .return_values_on_condition_check_failure(
+                        ReturnValuesOnConditionCheckFailure::AllOld,
+                    )
+                    .build()
+                    .expect("all fields set");
+                TransactWriteItem::builder().delete(delete).build()
+            })
+            .collect();
+
+        let delete = self
+            .client
+            .transact_write_items()
+            .set_transact_items(Some(items))
+            .send()
+            .await;
+        match delete.map_err(TransactFailure::from) {
+            Err(TransactFailure::ConditionFailed { item, .. }) => {
+                Err(self.lost_error(item.as_ref()))
+            }
+            Err(TransactFailure::Conflict(err) | TransactFailure::Other(err)) => Err(err),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Put a new shared lease into the db, joining existing shared holders if any.
+    #[instrument(skip_all)]
+    async fn put_shared_lease(&self, lease: NewLease) -> Result<Option<u64>, Error> {
+        self.check_key(&lease.key)?;
+        // retry joining once in case of a race with another shared holder creating the item
+        for _ in 0..2 {
+            match self
+                .set_reader(&lease.key, None, lease.lease_v, lease.expiry, lease.now)
+                .await
+            {
+                Ok(item) => return self.fencing_token(Some(&item)).map(Some),
+                Err(err) if err.is_lease_lost() => {}
+                Err(err) => return Err(err),
+            }
+            // no shared holders, so try to replace any expired lease
+            if let PutLease::Acquired { fencing_token } = self.put(lease.clone(), true).await? {
+                return Ok(Some(fencing_token));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Extends an active lease.
+    #[instrument(skip_all)]
+    async fn extend_lease(
+        &self,
+        key: &str,
+        lease_v: Uuid,
+        new_lease_v: Uuid,
+        expiry: i64,
+    ) -> Result<(), Error> {
+        self.update_held(self.extend_update(key, lease_v, new_lease_v, expiry))
+            .await
+    }
+
+    /// Adopts an active lease, extending it & setting the owner.
+    #[instrument(skip_all)]
+    async fn adopt_lease(
+        &self,
+        key: &str,
+        lease_v: Uuid,
+        new_lease_v: Uuid,
+        expiry: i64,
+        owner: &str,
+    ) -> Result<(), Error> {
+        let mut update = self.extend_update(key, lease_v, new_lease_v, expiry);
+        update.update_expression.push_str(", #owner = :owner");
+        update
+            .names
+            .insert("#owner".into(), self.names.lease_owner.clone());
+        update
+            .values
+            .insert(":owner".into(), AttributeValue::S(owner.into()));
+        self.update_held(update).await
+    }
+
+    /// Delete a lease with a given `key` & `lease_v`.
+    #[instrument(skip_all)]
+    async fn delete_lease(&self, key: &str, lease_v: Uuid) -> Result<(), Error> {
+        let delete = self
+            .client
+            .delete_item()
+            .table_name(self.table_name.as_str())
+            .set_key(Some(self.item_key(key)))
+            .condition_expression("#lv = :lease_v")
+            .expression_attribute_names("#lv", &self.names.lease_version)
+            .expression_attribute_values(":lease_v", AttributeValue::S(lease_v.to_string()))
+            .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld)
+            .send()
+            .await;
+
+        match delete {
+            Err(SdkError::ServiceError(se))
+                if matches!(
+                    se.err(),
+                    DeleteItemError::ConditionalCheckFailedException(..)
+                ) =>
+            {
+                let old_item = match se.err() {
+                    DeleteItemError::ConditionalCheckFailedException(err) => err.item(),
+                    _ => None,
+                };
+                Err(self.lost_error(old_item))
+            }
+            Err(err) => Err(Error::from_sdk("DeleteItem", err)),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Extends an active shared lease.
+    #[instrument(skip_all)]
+    async fn extend_shared_lease(
+        &self,
+        key: &str,
+        lease_v: Uuid,
+        new_lease_v: Uuid,
+        expiry: i64,
+        now: i64,
+    ) -> Result<(), Error> {
+        self.set_reader(key, Some(lease_v), new_lease_v, expiry, now)
+            .await?;
+        Ok(())
+    }
+
+    /// Puts or refreshes a fair queue ticket, creating the queue item if necessary.
+    #[instrument(skip_all)]
+    async fn put_ticket(
+        &self,
+        key: &str,
+        ticket: &str,
+        expiry: i64,
+    ) -> Result<BTreeMap<String, i64>, Error> {
+        let names = &self.names;
+        let expiry_value = AttributeValue::N(expiry.to_string());
+        // retry in case of a race with another waiter creating or deleting the queue
+        for _ in 0..PUT_TICKET_ATTEMPTS {
+            let update = self
+                .client
+                .update_item()
+                .table_name(self.table_name.as_str())
+                .set_key(Some(self.internal_item_key("queue", key)))
+                .update_expression("SET #queue.#ticket = :expiry, #le = :expiry")
+                .condition_expression("attribute_exists(#queue)")
+                .expression_attribute_names("#queue", &names.lease_queue)
+                .expression_attribute_names("#ticket", ticket)
+                .expression_attribute_names("#le", &names.lease_expiry)
+                .expression_attribute_values(":expiry", expiry_value.clone())
+                .return_values(ReturnValue::AllNew)
+                .send()
+                .await;
+            match update {
+                Err(SdkError::ServiceError(se))
+                    if matches!(
+                        se.err(),
+                        UpdateItemError::ConditionalCheckFailedException(..)
+                    ) => {}
+                Err(err) => return Err(Error::from_sdk("UpdateItem", err)),
+                Ok(out) => return Ok(self.queue_tickets(&out.attributes.unwrap_or_default())),
+            }
+
+            // no queue, so create it
+            let queue = [(ticket.to_owned(), expiry_value.clone())];
+            let create = self
+                .client
+                .update_item()
+                .table_name(self.table_name.as_str())
+                .set_key(Some(self.internal_item_key("queue", key)))
+                .update_expression("SET #queue = :queue, #le = :expiry")
+                .condition_expression("attribute_not_exists(#queue)")
+                .expression_attribute_names("#queue", &names.lease_queue)
+                .expression_attribute_names("#le", &names.lease_expiry)
+                .expression_attribute_values(":queue", AttributeValue::M(queue.into()))
+                .expression_attribute_values(":expiry", expiry_value.clone())
+                .send()
+                .await;
+            match create {
+                Err(SdkError::ServiceError(se))
+                    if matches!(
+                        se.err(),
+                        UpdateItemError::ConditionalCheckFailedException(..)
+                    ) => {}
+                Err(err) => return Err(Error::from_sdk("UpdateItem", err)),
+                Ok(_) => return Ok([(ticket.to_owned(), expiry)].into()),
+            }
+        }
+        Err(Error::Db {
+            operation: "UpdateItem",
+            retryable: true,
+            source: "fair queue concurrently created & deleted".into(),
+        })
+    }
+
+    /// Removes fair queue tickets, deleting the queue item if no tickets remain.
+    #[instrument(skip_all)]
+    async fn delete_tickets(&self, key: &str, tickets: &[&str]) -> Result<(), Error> {
+        if tickets.is_empty() {
+            return Ok(());
+        }
+        let names = &self.names;
+        let remove: Vec<_> = (0..tickets.len())
+            .map(|idx| format!("#queue.#t{idx}"))
+            .collect();
+        let mut update = self
+            .client
+            .update_item()
+            .table_name(self.table_name.as_str())
+            .set_key(Some(self.internal_item_key("queue", key)))
+            .update_expression(format!("REMOVE {}", remove.join(", ")))
+            .condition_expression("attribute_exists(#queue)")
+            .expression_attribute_names("#queue", &names.lease_queue)
+            .return_values(ReturnValue::AllNew);
+        for (idx, ticket) in tickets.iter().enumerate() {
+            update = update.expression_attribute_names(format!("#t{idx}"), *ticket);
+        }
+
+        let item = match update.send().await {
+            Err(SdkError::ServiceError(se))
+                if matches!(
+                    se.err(),
+                    UpdateItemError::ConditionalCheckFailedException(..)
+                ) =>
+            {
+                return Ok(());
+            }
+            Err(err) => return Err(Error::from_sdk("UpdateItem", err)),
+            Ok(out) => out.attributes.unwrap_or_default(),
+        };
+
+        if self.queue_tickets(&item).is_empty() {
+            // delete the queue, unless another waiter has since joined
+            let delete = self
+                .client
+                .delete_item()
+                .table_name(self.table_name.as_str())
+                .set_key(Some(self.internal_item_key("queue", key)))
+                .condition_expression("size(#queue) = :zero")
+                .expression_attribute_names("#queue", &names.lease_queue)
+                .expression_attribute_values(":zero", AttributeValue::N("0".into()))
+                .send()
+                .await;
+            match delete {
+                Err(SdkError::ServiceError(se))
+                    if matches!(
+                        se.err(),
+                        DeleteItemError::ConditionalCheckFailedException(..)
+                    ) => {}
+                Err(err) => return Err(Error::from_sdk("DeleteItem", err)),
+                Ok(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete a shared lease with a given `key` & `lease_v`, deleting the item if no
+    /// other shared holders remain.
+    #[instrument(skip_all)]
+    async fn delete_shared_lease(&self, key: &str, lease_v: Uuid) -> Result<(), Error> {
+        let names = &self.names;
+        let update = self
+            .client
+            .update_item()
+            .table_name(self.table_name.as_str())
+            .set_key(Some(self.item_key(key)))
+            .update_expression("REMOVE #readers.#old")
+            .condition_expression("attribute_exists(#readers.#old)")
+            .expression_attribute_names("#readers", &names.lease_readers)
+            .expression_attribute_names("#old", lease_v.to_string())
+            .return_values(ReturnValue::AllNew)
+            .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld)
+            .send()
+            .await;
+
+        let item = match update {
+            Err(SdkError::ServiceError(se))
+                if matches!(
+                    se.err(),
+                    UpdateItemError::ConditionalCheckFailedException(..)
+                ) =>
+            {
+                let old_item = match se.err() {
+                    UpdateItemError::ConditionalCheckFailedException(err) => err.item(),
+                    _ => None,
+                };
+                return Err(self.lost_error(old_item));
+            }
+            Err(err) => return Err(Error::from_sdk("UpdateItem", err)),
+            Ok(out) => out.attributes.unwrap_or_default(),
+        };
+
+        let no_readers = item
+            .get(&names.lease_readers)
+            .and_then(|v| v.as_m().ok())
+            .is_some_and(|readers| readers.is_empty());
+        if no_readers {
+            // delete the item, unless another shared holder has since joined
+            let delete = self
+                .client
+                .delete_item()
+                .table_name(self.table_name.as_str())
+                .set_key(Some(self.item_key(key)))
+                .condition_expression("size(#readers) = :zero")
+                .expression_attribute_names("#readers", &names.lease_readers)
+                .expression_attribute_values(":zero", AttributeValue::N("0".into()))
+                .send()
+                .await;
+            match delete {
+                Err(SdkError::ServiceError(se))
+                    if matches!(
+                        se.err(),
+                        DeleteItemError::ConditionalCheckFailedException(..)
+                    ) => {}
+                Err(err) => return Err(Error::from_sdk("DeleteItem", err)),
+                Ok(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Revokes a lease, marking it expired with a new version rather than deleting it,
+    /// so the evicted holder can find out who revoked it.
+    #[instrument(skip_all)]
+    async fn revoke_lease(
+        &self,
+        key: &str,
+        lease_v: Option<Uuid>,
+        revoked_by: &str,
+        expiry: i64,
+    ) -> Result<(), Error> {
+        self.check_key(key)?;
+        let mut revoke = self
+            .client
+            .update_item()
+            .table_name(self.table_name.as_str())
+            .set_key(Some(self.item_key(key)))
+            .update_expression(
+                "SET #lv = :new_lease_v, #le = :expiry, #revoked = :revoked_by REMOVE #readers",
+            )
+            .expression_attribute_names("#lv", &self.names.lease_version)
+            .expression_attribute_names("#readers", &self.names.lease_readers)
+            .expression_attribute_names("#le", &self.names.lease_expiry)
+            .expression_attribute_names("#revoked", &self.names.lease_revoked_by)
+            .expression_attribute_values(
+                ":new_lease_v",
+                AttributeValue::S(Uuid::new_v4().to_string()),
+            )
+            .expression_attribute_values(":expiry", AttributeValue::N(expiry.to_string()))
+            .expression_attribute_values(":revoked_by", AttributeValue::S(revoked_by.into()));
+        revoke = match lease_v {
+            Some(lease_v) => revoke
+                .condition_expression("#lv = :lease_v")
+                .expression_attribute_values(":lease_v", AttributeValue::S(lease_v.to_string())),
+            None => revoke.condition_expression("attribute_exists(#lv)"),
+        };
+
+        match revoke.send().await {
+            Err(SdkError::ServiceError(se))
+                if matches!(
+                    se.err(),
+                    UpdateItemError::ConditionalCheckFailedException(..)
+                ) =>
+            {
+                Err(Error::LeaseLost)
+            }
+            Err(err) => Err(Error::from_sdk("UpdateItem", err)),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Gets a lease with a strongly consistent read.
+    #[instrument(skip_all)]
+    async fn get_lease(&self, key: &str) -> Result<Option<LeaseInfo>, Error> {
+        let get = self
+            .client
+            .get_item()
+            .table_name(self.table_name.as_str())
+            .set_key(Some(self.item_key(key)))
+            .consistent_read(true)
+            .send()
+            .await;
+
+        let item = get.map_err(|err| Error::from_sdk("GetItem", err))?.item;
+        item.map(|item| self.lease_info(key.into(), item))
+            .transpose()
+    }
+
+    /// Lists leases using paginated _Scan_, or _Query_ for a [`KeySplit::FixedPartition`],
+    /// with strongly consistent reads.
+    fn list_leases(&self, options: &ListOptions) -> BoxStream<'static, Result<LeaseInfo, Error>> {
+        let store = self.clone();
+        let options = options.clone();
+        // `None` once the last page has been fetched
+        let pages = stream::try_unfold(Some(None), move |start| {
+            let store = store.clone();
+            let options = options.clone();
+            async move {
+                let Some(start) = start else { return Ok(None) };
+                let (items, last_key) = store.list_page(&options, start).await?;
+                let leases: Vec<_> = items
+                    .into_iter()
+                    .map(|item| store.lease_info(store.listed_lease_key(&item)?, item))
+                    .collect();
+                Ok(Some((stream::iter(leases), last_key.map(Some))))
+            }
+        });
+        pages.try_flatten().boxed()
+    }
+
+    /// Checks table is active & has a valid schema.
+    async fn check_schema(&self) -> Result<(), Error> {
+        let key_field = self.names.key.as_str();
+        let lease_expiry_field = self.names.lease_expiry.as_str();
+
+        // fetch table & ttl descriptions concurrently
+        let (table_desc, ttl_desc) = tokio::join!(
+            self.client
+                .describe_table()
+                .table_name(self.table_name.as_str())
+                .send(),
+            self.client
+                .describe_time_to_live()
+                .table_name(self.table_name.as_str())
+                .send()
+        );
+
+        let desc = match table_desc {
+            Err(SdkError::ServiceError(se))
+                if matches!(se.err(), DescribeTableError::ResourceNotFoundException(..)) =>
+            {
+                return Err(Error::TableNotFound {
+                    table_name: self.table_name.to_string(),
+                    source: SdkError::ServiceError(se).into(),
+                });
+            }
+            Err(err) => return Err(Error::from_sdk("DescribeTable", err)),
+            Ok(out) => out
+                .table
+                .ok_or_else(|| Error::InvalidSchema("no table description".into()))?,
+        };
+
+        // check key field is a S hash key, & if configured the sort key field is a S range key
+        let attrs = desc.attribute_definitions.unwrap_or_default();
+        let key_schema = desc.key_schema.unwrap_or_default();
+        let expected_keys = match &self.sort_key {
+            Some(sort_key) => vec![
+                (key_field, KeyType::Hash),
+                (sort_key.attribute_name.as_str(), KeyType::Range),
+            ],
+            None => vec![(key_field, KeyType::Hash)],
+        };
+        ensure(key_schema.len() == expected_keys.len(), || {
+            format!(
+                "Unexpected number of keys ({}) in key_schema, expected {}. Got {:?}",
+                key_schema.len(),
+                expected_keys.len(),
+                vec(key_schema.iter().map(|k| k.attribute_name())),
+            )
+        })?;
+        for (field, key_type) in expected_keys {
+            check_key_field(&attrs, &key_schema, field, key_type)?;
+        }
+
+        // check lease expiry field is a ttl field
+        let update_time_to_live_desc = ttl_desc
+            .map_err(|err| Error::from_sdk("DescribeTimeToLive", err))?
+            .time_to_live_description
+            .ok_or_else(|| Error::InvalidSchema("no time to live description".into()))?;
+
+        ensure(
+            update_time_to_live_desc.attribute_name() == Some(lease_expiry_field),
+            || format!("time to live for {lease_expiry_field} is not set"),
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Returns [`Error::InvalidSchema`] if `condition` is false.
+#[inline]
+fn ensure(condition: bool, msg: impl FnOnce() -> String) -> Result<(), Error> {
+    match condition {
+        true => Ok(()),
+        false => Err(Error::InvalidSchema(msg())),
+    }
+}
+
+#[inline]
+fn vec<T>(iter: impl Iterator<Item = T>) -> Vec<T> {
+    iter.collect()
+}
+
+/// Checks `field` is a S attribute with the given `key_type`.
+fn check_key_field(
+    attrs: &[AttributeDefinition],
+    key_schema: &[KeySchemaElement],
+    field: &str,
+    key_type: KeyType,
+) -> Result<(), Error> {
+    let described_kind = attrs
+        .iter()
+        .find(|attr| attr.attribute_name() == field)
+        .ok_or_else(|| {
+            Error::InvalidSchema(format!(
+                "Missing attribute definition for {field}, available {:?}",
+                vec(attrs.iter().map(|a| a.attribute_name()))
+            ))
+        })?
+        .attribute_type();
+    ensure(described_kind == &ScalarAttributeType::S, || {
+        format!(
+            "Unexpected attribute type `{:?}` for {}, expected `{:?}`",
+            described_kind,
+            field,
+            ScalarAttributeType::S,
+        )
+    })?;
+
+    let described_key_type = key_schema
+        .iter()
+        .find(|k| k.attribute_name() == field)
+        .ok_or_else(|| {
+            Error::InvalidSchema(format!(
+                "Missing key schema for {field}, available {:?}",
+                vec(key_schema.iter().map(|k| k.attribute_name()))
+            ))
+        })?
+        .key_type();
+    ensure(described_key_type == &key_type, || {
+        format!("Unexpected key type `{described_key_type:?}` for {field}, expected `{key_type:?}`",)
+    })
+}
@@ -0,0 +1,149 @@
+use crate::retry::is_transient;
+use aws_sdk_dynamodb::error::{ProvideErrorMetadata, SdkError};
+use aws_smithy_runtime_api::client::orchestrator::HttpResponse;
+use std::time::Duration;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Service error codes that indicate a request was throttled.
+const THROTTLING_ERROR_CODES: &[&str] = &[
+    "ThrottlingException",
+    "ProvisionedThroughputExceededException",
+    "RequestLimitExceeded",
+];
+
+/// Error returned by [`crate::Client`], [`crate::Lease`] & [`crate::ClientBuilder`] methods.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// The lease could not be acquired within the max wait duration.
+    #[error("Could not acquire within {0:?}")]
+    Timeout(Duration),
+    /// The lease table does not exist.
+    #[error("Missing table `{table_name}`?")]
+    TableNotFound {
+        table_name: String,
+        #[source]
+        source: BoxError,
+    },
+    /// The lease table is not active or does not have the expected schema.
+    #[error("{0}")]
+    InvalidSchema(String),
+    /// A dynamodb request was throttled.
+    #[error("dynamodb {operation} throttled")]
+    Throttled {
+        operation: &'static str,
+        #[source]
+        source: BoxError,
+    },
+    /// The lease has already been deleted or replaced, i.e. it is no longer held.
+    #[error("lease lost, deleted or replaced")]
+    LeaseLost,
+    /// The lease was revoked by [`crate::Client::force_release`], i.e. it is no longer held.
+    #[error("lease revoked by {revoked_by}")]
+    LeaseRevoked { revoked_by: String },
+    /// The operation is not supported by the store or its configuration.
+    #[error("unsupported: {0}")]
+    Unsupported(String),
+    /// A lease token could not be parsed, i.e. a [`crate::HandoffToken`] or [`crate::LeaseIdentity`].
+    #[error("invalid lease token `{0}`")]
+    InvalidToken(String),
+    /// Invalid options were provided, e.g. [`crate::AcquireOptions`].
+    #[error("invalid options: {0}")]
+    InvalidOptions(String),
+    /// The lease key is reserved for internal items, i.e. starts with `~lease/`.
+    #[error("invalid lease key `{0}`, reserved for internal items")]
+    InvalidKey(String),
+    /// Any other dynamodb request failure.
+    #[error("dynamodb {operation} failed")]
+    Db {
+        operation: &'static str,
+        retryable: bool,
+        #[source]
+        source: BoxError,
+    },
+}
+
+impl Error {
+    /// Returns `true` if the error is likely transient, e.g. throttling, 5xx responses
+    /// or timeouts, so the operation may succeed if retried.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Throttled { .. } => true,
+            Self::Db { retryable, .. } => *retryable,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the lease could not be acquired within the max wait duration.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, Self::Timeout(..))
+    }
+
+    /// Returns `true` if the lease table does not exist.
+    pub fn is_table_not_found(&self) -> bool {
+        matches!(self, Self::TableNotFound { .. })
+    }
+
+    /// Returns `true` if the lease table is not active or does not have the expected schema.
+    pub fn is_invalid_schema(&self) -> bool {
+        matches!(self, Self::InvalidSchema(..))
+    }
+
+    /// Returns `true` if a dynamodb request was throttled.
+    pub fn is_throttled(&self) -> bool {
+        matches!(self, Self::Throttled { .. })
+    }
+
+    /// Returns `true` if the lease has already been deleted, replaced or revoked.
+    pub fn is_lease_lost(&self) -> bool {
+        matches!(self, Self::LeaseLost | Self::LeaseRevoked { .. })
+    }
+
+    /// Returns `true` if the lease was revoked by [`crate::Client::force_release`].
+    pub fn is_lease_revoked(&self) -> bool {
+        matches!(self, Self::LeaseRevoked { .. })
+    }
+
+    /// Returns `true` if the operation is not supported by the store or its configuration.
+    pub fn is_unsupported(&self) -> bool {
+        matches!(self, Self::Unsupported(..))
+    }
+
+    /// Returns `true` if a lease token could not be parsed.
+    pub fn is_invalid_token(&self) -> bool {
+        matches!(self, Self::InvalidToken(..))
+    }
+
+    /// Returns `true` if invalid options were provided.
+    pub fn is_invalid_options(&self) -> bool {
+        matches!(self, Self::InvalidOptions(..))
+    }
+
+    /// Returns `true` if the lease key is reserved for internal items.
+    pub fn is_invalid_key(&self) -> bool {
+        matches!(self, Self::InvalidKey(..))
+    }
+
+    /// Classifies an sdk error from the given dynamodb `operation`.
+    pub(crate) fn from_sdk<E>(operation: &'static str, err: SdkError<E, HttpResponse>) -> Self
+    where
+        E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+    {
+        let throttled = err
+            .as_service_error()
+            .and_then(|e| e.code())
+            .is_some_and(|code| THROTTLING_ERROR_CODES.contains(&code));
+        match throttled {
+            true => Self::Throttled {
+                operation,
+                source: err.into(),
+            },
+            false => Self::Db {
+                operation,
+                retryable: is_transient(&err),
+                source: err.into(),
+            },
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{Error, lease::LeaseKind};
+use std::{fmt, str::FromStr};
+use uuid::Uuid;
+
+/// A held lease, with local extension stopped, that may be adopted by another client
+/// or process, see [`crate::Lease::into_handoff_token`] & [`crate::Client::adopt`].
+///
+/// Serializes to & from a string with [`fmt::Display`] & [`FromStr`],
+/// e.g. to pass to another process.
+///
+/// The lease is no longer extended by anyone until adopted, so must be adopted
+/// before it expires.
+///
+/// # Example
+/// ```
+/// # async fn foo(lease: dynamodb_lease::Lease, client: dynamodb_lease::Client) -> Result<(), dynamodb_lease::Error> {
+/// let token = lease.into_handoff_token().await.to_string();
+///
+/// // in another process
+/// let lease = client.adopt(token.parse()?).await?;
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandoffToken {
+    pub(crate) key: String,
+    pub(crate) kind: LeaseKind,
+    pub(crate) fencing_token: u64,
+    pub(crate) lease_v: Uuid,
+}
+
+impl HandoffToken {
+    /// Returns the key of the handed off lease.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the fencing token of the handed off lease, see [`crate::Lease::fencing_token`].
+    pub fn fencing_token(&self) -> u64 {
+        self.fencing_token
+    }
+}
+
+impl fmt::Display for HandoffToken {
+    /// Formats as `{kind}:{fencing_token}:{lease_v}:{key}`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}:{}",
+            kind_str(self.kind),
+            self.fencing_token,
+            self.lease_v,
+            self.key
+        )
+    }
+}
+
+impl FromStr for HandoffToken {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidToken(s.into());
+        // key is last as it may contain ':'
+        let mut parts = s.splitn(4, ':');
+        let (kind, fencing_token, lease_v) = parse_prefix(&mut parts).ok_or_else(invalid)?;
+        let key = parts.next().ok_or_else(invalid)?.into();
+        Ok(Self {
+            key,
+            kind,
+            fencing_token,
+            lease_v,
+        })
+    }
+}
+
+/// Identity of a held lease, that may be persisted so the holder can reclaim the lease
+/// after restarting, see [`crate::Lease::watch_identity`] & [`crate::Client::reclaim`].
+///
+/// The `lease_v` changes each time the lease is extended, so the latest identity must
+/// be persisted. Serializes to & from a string with [`fmt::Display`] & [`FromStr`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeaseIdentity {
+    /// The lease key.
+    pub key: String,
+    /// The current `lease_version`.
+    pub lease_v: Uuid,
+    /// The lease owner, see [`crate::Client::owner`].
+    pub owner: String,
+    /// The lease fencing token, see [`crate::Lease::fencing_token`].
+    pub fencing_token: u64,
+    /// Whether this is a shared lease, see [`crate::Client::acquire_shared`].
+    pub shared: bool,
+}
+
+impl LeaseIdentity {
+    pub(crate) fn kind(&self) -> LeaseKind {
+        match self.shared {
+            true => LeaseKind::Shared,
+            false => LeaseKind::Exclusive,
+        }
+    }
+}
+
+impl fmt::Display for LeaseIdentity {
+    /// Formats as `{kind}:{fencing_token}:{lease_v}:{owner_len}:{owner}:{key}`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}:{}:{}:{}",
+            kind_str(self.kind()),
+            self.fencing_token,
+            self.lease_v,
+            self.owner.len(),
+            self.owner,
+            self.key
+        )
+    }
+}
+
+impl FromStr for LeaseIdentity {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidToken(s.into());
+        // owner & key may contain ':', so owner is length prefixed & key is last
+        let mut parts = s.splitn(5, ':');
+        let (kind, fencing_token, lease_v) = parse_prefix(&mut parts).ok_or_else(invalid)?;
+        let owner_len: usize = parts
+            .next()
+            .and_then(|l| l.parse().ok())
+            .ok_or_else(invalid)?;
+        let rest = parts.next().ok_or_else(invalid)?;
+        let owner = rest.get(..owner_len).ok_or_else(invalid)?;
+        let key = rest
+            .get(owner_len..)
+            .and_then(|k| k.strip_prefix(':'))
+            .ok_or_else(invalid)?;
+        Ok(Self {
+            key: key.into(),
+            lease_v,
+            owner: owner.into(),
+            fencing_token,
+            shared: kind == LeaseKind::Shared,
+        })
+    }
+}
+
+fn kind_str(kind: LeaseKind) -> &'static str {
+    match kind {
+        LeaseKind::Exclusive => "exclusive",
+        LeaseKind::Shared => "shared",
+    }
+}
+
+/// Parses the common `{kind}:{fencing_token}:{lease_v}` prefix of tokens.
+fn parse_prefix<'a>(parts: &mut impl Iterator<Item = &'a str>) -> Option<(LeaseKind, u64, Uuid)> {
+    let kind = match parts.next()? {
+        "exclusive" => LeaseKind::Exclusive,
+        "shared" => LeaseKind::Shared,
+        _ => return None,
+    };
+    let fencing_token = parts.next()?.parse().ok()?;
+    let lease_v = parts.next()?.parse().ok()?;
+    Some((kind, fencing_token, lease_v))
+}
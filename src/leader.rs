@@ -0,0 +1,167 @@
+use crate::{Client, Error, Lease};
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{debug, warn};
+
+/// Leadership of a [`LeaderElection`] candidate, see [`LeaderElection::watch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Leadership {
+    /// This candidate is the leader, i.e. holds the election lease.
+    Leader {
+        /// Fencing token of the election lease, see [`Lease::fencing_token`].
+        fencing_token: u64,
+    },
+    /// This candidate is not the leader.
+    Follower {
+        /// Id of the current leader, if any, as last observed.
+        leader: Option<String>,
+    },
+}
+
+impl Leadership {
+    /// Returns `true` if this candidate is the leader.
+    pub fn is_leader(&self) -> bool {
+        matches!(self, Self::Leader { .. })
+    }
+}
+
+/// A candidate in a leader election, see [`Client::leader_election`].
+///
+/// A background task continuously tries to acquire the election lease of the key,
+/// observing the current leader while waiting. Once acquired the lease is extended
+/// until lost, after which the candidate tries to acquire it again.
+///
+/// On drop stops the candidacy & asynchronously releases leadership, if held.
+#[derive(Debug)]
+pub struct LeaderElection {
+    key: String,
+    candidate_id: String,
+    leadership: watch::Receiver<Leadership>,
+    stop: watch::Sender<bool>,
+    task: Option<JoinHandle<Result<(), Error>>>,
+}
+
+impl LeaderElection {
+    pub(crate) fn start(client: Client, key: String) -> Self {
+        let candidate_id = client.owner().to_owned();
+        let (leadership_tx, leadership) = watch::channel(Leadership::Follower { leader: None });
+        let (stop, stop_rx) = watch::channel(false);
+        let task = tokio::spawn(campaign(client, key.clone(), leadership_tx, stop_rx));
+        Self {
+            key,
+            candidate_id,
+            leadership,
+            stop,
+            task: Some(task),
+        }
+    }
+
+    /// Returns the key of the election lease.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the id of this candidate, stored as the owner of the election lease.
+    pub fn candidate_id(&self) -> &str {
+        &self.candidate_id
+    }
+
+    /// Returns `true` if this candidate is currently the leader.
+    pub fn is_leader(&self) -> bool {
+        self.leadership.borrow().is_leader()
+    }
+
+    /// Returns the current [`Leadership`] of this candidate.
+    pub fn leadership(&self) -> Leadership {
+        self.leadership.borrow().clone()
+    }
+
+    /// Returns a receiver that observes each [`Leadership`] change, including changes
+    /// of the observed leader while a follower.
+    pub fn watch(&self) -> watch::Receiver<Leadership> {
+        self.leadership.clone()
+    }
+
+    /// Stops the candidacy, releasing leadership if held. Returns `Ok(())` after
+    /// successful release, so another candidate may immediately become leader.
+    ///
+    /// # Errors
+    /// Returns [`Error::LeaseLost`] if leadership was held but the lease had already
+    /// been lost, see [`Lease::release`].
+    pub async fn step_down(mut self) -> Result<(), Error> {
+        self.stop.send_replace(true);
+        match self.task.take() {
+            Some(task) => task
+                .await
+                .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for LeaderElection {
+    /// Stops the candidacy, the background task asynchronously releases leadership.
+    fn drop(&mut self) {
+        self.stop.send_replace(true);
+    }
+}
+
+/// Outcome of holding leadership.
+enum Held {
+    Lost,
+    Stopped,
+}
+
+/// Tries to become leader, then holds leadership until lost, until stopped.
+async fn campaign(
+    client: Client,
+    key: String,
+    leadership: watch::Sender<Leadership>,
+    mut stop: watch::Receiver<bool>,
+) -> Result<(), Error> {
+    loop {
+        let lease = tokio::select! {
+            lease = follow(&client, &key, &leadership) => lease,
+            _ = stop.wait_for(|stop| *stop) => return Ok(()),
+        };
+        debug!(key, "elected leader");
+        let fencing_token = lease.fencing_token();
+        leadership.send_replace(Leadership::Leader { fencing_token });
+
+        let held = tokio::select! {
+            reason = lease.lost() => {
+                warn!(key, ?reason, "leadership lost");
+                Held::Lost
+            }
+            _ = stop.wait_for(|stop| *stop) => Held::Stopped,
+        };
+        leadership.send_replace(Leadership::Follower { leader: None });
+        if let Held::Stopped = held {
+            debug!(key, "stepping down");
+            return lease.release().await;
+        }
+    }
+}
+
+/// Polls to acquire the election lease, observing the current leader meanwhile.
+async fn follow(client: &Client, key: &str, leadership: &watch::Sender<Leadership>) -> Lease {
+    loop {
+        match client.try_acquire(key).await {
+            Ok(Some(lease)) => return lease,
+            Ok(None) => match client.describe(key).await {
+                Ok(info) => {
+                    let leader = info.filter(|i| !i.is_expired()).and_then(|i| i.owner);
+                    let new = Leadership::Follower { leader };
+                    leadership.send_if_modified(|current| {
+                        let modified = *current != new;
+                        *current = new;
+                        modified
+                    });
+                }
+                Err(err) => warn!(key, "failed to describe leader: {err}"),
+            },
+            Err(err) => warn!(key, "failed to acquire leadership: {err}"),
+        }
+        tokio::time::sleep(client.acquire_cooldown).await;
+    }
+}
@@ -1,6 +1,15 @@
-use crate::Client;
-use std::sync::Arc;
-use tokio::sync::{Mutex, OwnedMutexGuard};
+use crate::{Client, Error, HandoffToken, LeaseIdentity};
+use std::{
+    cmp::min,
+    sync::{
+        Arc, Weak,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use time::OffsetDateTime;
+use tokio::sync::{Mutex, OwnedMutexGuard, watch};
+use tracing::{debug, error, warn};
 use uuid::Uuid;
 
 /// Represents a held distributed lease & background task to
@@ -10,24 +19,137 @@ use uuid::Uuid;
 #[derive(Debug)]
 pub struct Lease {
     client: Client,
-    key_lease_v: Arc<(String, Mutex<Uuid>)>,
+    state: Arc<LeaseState>,
     /// A local guard to avoid db contention for leases within the same client.
     local_guard: Option<OwnedMutexGuard<()>>,
     release_on_drop: bool,
 }
 
+/// Current status of a [`Lease`], see [`Lease::watch_status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LeaseStatus {
+    /// The lease is held and the last extension, if any, succeeded.
+    Held,
+    /// The last extension attempt failed but the lease has not yet expired,
+    /// so extension will be retried.
+    Extending,
+    /// The lease is no longer held. Another process may now acquire it.
+    Lost { reason: LostReason },
+}
+
+/// Why a [`Lease`] was lost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LostReason {
+    /// The lease item was deleted or replaced, i.e. `lease_version` no longer matched.
+    Replaced,
+    /// The lease expired before it could be successfully extended.
+    Expired,
+    /// The lease was revoked by [`Client::force_release`].
+    Revoked,
+}
+
+/// Whether a lease is exclusive or shared, see [`Client::acquire_shared`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LeaseKind {
+    Exclusive,
+    Shared,
+}
+
+#[derive(Debug)]
+struct LeaseState {
+    key: String,
+    kind: LeaseKind,
+    fencing_token: u64,
+    /// Current version, held during db interactions to avoid
+    /// races between extension & deletion.
+    version: Mutex<LeaseVersion>,
+    status: watch::Sender<LeaseStatus>,
+    /// Identity with the current version, updated after each extension.
+    identity: watch::Sender<LeaseIdentity>,
+    /// Set when the lease is being released, so should no longer be extended.
+    released: AtomicBool,
+}
+
+/// A confirmed `lease_version` & its `lease_expiry`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LeaseVersion {
+    pub(crate) lease_v: Uuid,
+    /// Unix timestamp (seconds).
+    pub(crate) expiry: i64,
+}
+
+impl LeaseVersion {
+    /// Expiry of this version as a timestamp.
+    fn expires_at(&self) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(self.expiry).unwrap_or(OffsetDateTime::UNIX_EPOCH)
+    }
+
+    /// Time until this version expires, according to the client's clock.
+    fn remaining(&self, client: &Client) -> Duration {
+        (self.expires_at() - client.clock.now())
+            .try_into()
+            .unwrap_or(Duration::ZERO)
+    }
+}
+
+impl LeaseState {
+    fn set_extended(&self, version: LeaseVersion) {
+        self.identity
+            .send_modify(|identity| identity.lease_v = version.lease_v);
+        self.status.send_replace(LeaseStatus::Held);
+    }
+
+    fn set_lost(&self, reason: LostReason) {
+        warn!(key = self.key, ?reason, "lease lost");
+        self.status.send_replace(LeaseStatus::Lost { reason });
+    }
+}
+
 impl Lease {
-    pub(crate) fn new(client: Client, key: String, lease_v: Uuid) -> Self {
-        let lease = Self {
+    pub(crate) fn new(
+        client: Client,
+        key: String,
+        kind: LeaseKind,
+        fencing_token: u64,
+        version: LeaseVersion,
+    ) -> Self {
+        let lease = Self::new_unextended(client, key, kind, fencing_token, version);
+        start_periodically_extending(lease.client.clone(), std::slice::from_ref(&lease));
+        lease
+    }
+
+    /// Returns a new lease without starting background extension, e.g. to extend
+    /// as part of a group with [`start_periodically_extending`].
+    pub(crate) fn new_unextended(
+        client: Client,
+        key: String,
+        kind: LeaseKind,
+        fencing_token: u64,
+        version: LeaseVersion,
+    ) -> Self {
+        let identity = LeaseIdentity {
+            key: key.clone(),
+            lease_v: version.lease_v,
+            owner: client.owner.to_string(),
+            fencing_token,
+            shared: kind == LeaseKind::Shared,
+        };
+        Self {
             client,
-            key_lease_v: Arc::new((key, Mutex::new(lease_v))),
+            state: Arc::new(LeaseState {
+                key,
+                kind,
+                fencing_token,
+                version: Mutex::new(version),
+                status: watch::Sender::new(LeaseStatus::Held),
+                identity: watch::Sender::new(identity),
+                released: AtomicBool::new(false),
+            }),
             local_guard: None,
             release_on_drop: true,
-        };
-
-        start_periodically_extending(&lease);
-
-        lease
+        }
     }
 
     pub(crate) fn with_local_guard(mut self, guard: OwnedMutexGuard<()>) -> Self {
@@ -45,51 +167,499 @@ impl Lease {
     /// If you await this method then immediately acquire a lease,
     /// e.g. inside a loop, you are acquiring with an unfair advantage vs other process
     /// attempts. This may lead to other process being starved of leases.
-    pub async fn release(mut self) -> anyhow::Result<()> {
+    ///
+    /// # Errors
+    /// Returns [`Error::LeaseLost`] if the lease had already been deleted or replaced,
+    /// or [`Error::LeaseRevoked`] if it had been revoked.
+    pub async fn release(mut self) -> Result<(), Error> {
         // disable release on drop since we're doing that now
         self.release_on_drop = false;
+        self.release_local();
 
-        let (key, lease_v) = &*self.key_lease_v;
+        let version = self.state.version.lock().await;
+        self.state.released.store(true, Ordering::Relaxed);
+        self.client
+            .delete_lease(&self.state.key, self.state.kind, version.lease_v)
+            .await?;
+        drop(version); // hold v-lock during deletion to ensure no race with `extend_lease`
+        Ok(())
+    }
+
+    /// Stops extending this lease & returns a token that may be used to adopt it,
+    /// without releasing it, by another client or process with [`Client::adopt`].
+    ///
+    /// The lease must then be adopted before it expires, after up to `lease_ttl_seconds`,
+    /// otherwise others may acquire it. This allows passing a lease to a new process,
+    /// e.g. during a rolling deploy, without a gap where others could acquire it.
+    pub async fn into_handoff_token(mut self) -> HandoffToken {
+        // disable release on drop since the lease is handed off
+        self.release_on_drop = false;
+        self.release_local();
 
+        let version = self.state.version.lock().await;
+        // stop extension, any in-flight extension has completed as the v-lock is held
+        self.state.released.store(true, Ordering::Relaxed);
+        HandoffToken {
+            key: self.state.key.clone(),
+            kind: self.state.kind,
+            fencing_token: self.state.fencing_token,
+            lease_v: version.lease_v,
+        }
+    }
+
+    /// Unlocks the local guard & cleans up local lock memory.
+    fn release_local(&mut self) {
         drop(self.local_guard.take());
-        self.client.try_clean_local_lock(key.clone());
+        self.client.try_clean_local_lock(self.state.key.clone());
+    }
 
-        let lease_v = lease_v.lock().await;
-        self.client.delete_lease(key.clone(), *lease_v).await?;
-        drop(lease_v); // hold v-lock during deletion to ensure no race with `extend_lease`
-        Ok(())
+    /// Releases the lease retrying transient errors according to the client's
+    /// `release_retry` policy, logging & counting the outcome.
+    async fn release_after_drop(mut self) {
+        self.release_local();
+
+        let key = self.state.key.as_str();
+        let version = self.state.version.lock().await;
+        self.state.released.store(true, Ordering::Relaxed);
+        // a `MultiLease` is lost when any of its keys is replaced or revoked, so the item of
+        // this key may still be held & the conditional delete is still attempted
+        if let LeaseStatus::Lost {
+            reason: reason @ LostReason::Expired,
+        } = self.status()
+        {
+            debug!(
+                key,
+                ?reason,
+                "dropped lease already lost, nothing to release"
+            );
+            return;
+        }
+
+        let policy = &self.client.release_retry;
+        let counters = &self.client.counters;
+        let mut attempt = 0;
+        loop {
+            match self
+                .client
+                .delete_lease(key, self.state.kind, version.lease_v)
+                .await
+            {
+                Ok(_) => {
+                    debug!(key, "released dropped lease");
+                    counters.drop_released();
+                    return;
+                }
+                Err(err) if err.is_lease_lost() => {
+                    debug!(
+                        key,
+                        "dropped lease already deleted, replaced or revoked: {err}"
+                    );
+                    return;
+                }
+                Err(err) if err.is_retryable() && attempt + 1 < policy.attempts() => {
+                    let backoff = policy.backoff(attempt);
+                    if backoff >= version.remaining(&self.client) {
+                        warn!(key, "failed to release dropped lease before expiry: {err}");
+                        counters.leaked();
+                        return;
+                    }
+                    warn!(
+                        key,
+                        attempt,
+                        ?backoff,
+                        "failed to release dropped lease, retrying: {err}"
+                    );
+                    counters.drop_release_retried();
+                    attempt += 1;
+                    tokio::time::sleep(backoff).await;
+                }
+                Err(err) => {
+                    error!(
+                        key,
+                        attempts = attempt + 1,
+                        "failed to release dropped lease, it will block others until expiry: {err}"
+                    );
+                    counters.leaked();
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Returns the key of this lease.
+    pub fn key(&self) -> &str {
+        &self.state.key
+    }
+
+    /// Returns `true` if this is a shared lease, see [`Client::acquire_shared`].
+    pub fn is_shared(&self) -> bool {
+        self.state.kind == LeaseKind::Shared
     }
 
     /// Get the unique UUID identifier for this lease instance.
     /// This UUID changes each time the lease is successfully extended.
     pub async fn lease_v(&self) -> Uuid {
-        *self.key_lease_v.1.lock().await
+        self.state.version.lock().await.lease_v
+    }
+
+    /// Extends this lease now, returning the new expiry.
+    ///
+    /// The lease is renewed to `ttl` if provided, rounded up to whole seconds, otherwise
+    /// the ttl it was acquired with. With automatic extension, see
+    /// [`crate::ClientBuilder::auto_extend`], later background extensions renew to the
+    /// acquired ttl again.
+    ///
+    /// # Errors
+    /// Returns [`Error::LeaseLost`] if the lease has already been lost, e.g. it expired
+    /// before being extended, or has been deleted or replaced, or [`Error::LeaseRevoked`]
+    /// if it has been revoked. The lease status is then [`LeaseStatus::Lost`].
+    ///
+    /// Returns [`Error::InvalidOptions`] if `ttl` is less than 2s.
+    /// Other errors, e.g. [`Error::is_retryable`], do not change the lease status.
+    pub async fn extend(&self, ttl: Option<Duration>) -> Result<OffsetDateTime, Error> {
+        let mut client = self.client.clone();
+        if let Some(ttl) = ttl {
+            let seconds = ttl.as_secs() + u64::from(ttl.subsec_nanos() > 0);
+            client.lease_ttl_seconds = u32::try_from(seconds)
+                .ok()
+                .filter(|s| *s >= 2)
+                .ok_or_else(|| Error::InvalidOptions("ttl must be at least 2s".into()))?;
+        }
+
+        let mut version = self.state.version.lock().await;
+        if let LeaseStatus::Lost { .. } = self.status() {
+            return Err(Error::LeaseLost);
+        }
+        if version.remaining(&client).is_zero() {
+            self.state.set_lost(LostReason::Expired);
+            return Err(Error::LeaseLost);
+        }
+
+        let extend = client
+            .extend_lease(&self.state.key, self.state.kind, version.lease_v)
+            .await;
+        match extend {
+            Ok(new_version) => {
+                *version = new_version;
+                self.state.set_extended(new_version);
+                Ok(new_version.expires_at())
+            }
+            Err(err) => {
+                match err {
+                    Error::LeaseLost => self.state.set_lost(LostReason::Replaced),
+                    Error::LeaseRevoked { .. } => self.state.set_lost(LostReason::Revoked),
+                    _ => {}
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Returns the fencing token for this lease.
+    ///
+    /// Each acquisition of a key atomically increments its token, so a newer holder
+    /// will always have a greater token than previous holders. Unlike [`Lease::lease_v`]
+    /// this does not change when the lease is extended.
+    ///
+    /// Pass this to downstream storage along with writes protected by the lease, so
+    /// writes from a stale holder (e.g. after a long GC pause or network partition)
+    /// can be rejected.
+    ///
+    /// The last token is stored separately to the lease, without expiry, so tokens keep
+    /// increasing after the lease is released or expires regardless of client clocks.
+    ///
+    /// # Cost
+    /// With [`crate::DynamoDbStore`] each acquisition reads the token with a consistent
+    /// _GetItem_ & writes the lease & token items with a 2 item _TransactWriteItems_,
+    /// roughly 4× the write capacity of a single put. Token items are never deleted,
+    /// so the table keeps one item per key ever leased.
+    pub fn fencing_token(&self) -> u64 {
+        self.state.fencing_token
+    }
+
+    /// Returns the current identity of this lease, which may be persisted to reclaim
+    /// the lease after a restart with [`Client::reclaim`].
+    ///
+    /// The identity's `lease_v` changes each time the lease is extended,
+    /// see [`Lease::watch_identity`].
+    pub fn identity(&self) -> LeaseIdentity {
+        self.state.identity.borrow().clone()
+    }
+
+    /// Returns a receiver that observes each [`LeaseIdentity`] change, i.e. after each
+    /// extension, so the latest identity can be persisted.
+    ///
+    /// # Example
+    /// ```
+    /// # async fn foo(lease: dynamodb_lease::Lease) {
+    /// # async fn persist(_: String) {}
+    /// let mut identity = lease.watch_identity();
+    /// tokio::spawn(async move {
+    ///     loop {
+    ///         let latest = identity.borrow_and_update().to_string();
+    ///         persist(latest).await;
+    ///         if identity.changed().await.is_err() {
+    ///             break;
+    ///         }
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    ///
+    /// Note: After the lease is dropped the receiver will observe no further changes.
+    pub fn watch_identity(&self) -> watch::Receiver<LeaseIdentity> {
+        self.state.identity.subscribe()
+    }
+
+    /// Returns the current status of this lease.
+    pub fn status(&self) -> LeaseStatus {
+        *self.state.status.borrow()
+    }
+
+    /// Returns a receiver that observes each [`LeaseStatus`] change.
+    ///
+    /// Note: After the lease is dropped the receiver will observe no further changes.
+    pub fn watch_status(&self) -> watch::Receiver<LeaseStatus> {
+        self.state.status.subscribe()
+    }
+
+    /// Waits until this lease is lost, returning why.
+    ///
+    /// A lease is lost if an extension finds the lease has been deleted, replaced or revoked
+    /// or if the lease expires before it can be successfully extended.
+    /// Once lost other processes may acquire the lease, so work that relies on
+    /// exclusivity should stop.
+    ///
+    /// # Example
+    /// ```
+    /// # async fn foo(lease: dynamodb_lease::Lease) {
+    /// # async fn do_work() {}
+    /// tokio::select! {
+    ///     _ = do_work() => {}
+    ///     reason = lease.lost() => eprintln!("lease lost: {reason:?}"),
+    /// }
+    /// # }
+    /// ```
+    pub async fn lost(&self) -> LostReason {
+        let mut status = self.state.status.subscribe();
+        let status = *status
+            .wait_for(|s| matches!(s, LeaseStatus::Lost { .. }))
+            .await
+            .expect("status sender is owned by the lease");
+        match status {
+            LeaseStatus::Lost { reason } => reason,
+            _ => unreachable!(),
+        }
     }
 }
 
-fn start_periodically_extending(lease: &Lease) {
-    let key_lease_v = Arc::downgrade(&lease.key_lease_v);
-    let client = lease.client.clone();
+/// Periodically extends a group of leases in a background task until dropped, released
+/// or lost. A group of multiple leases, see [`crate::MultiLease`], is extended atomically.
+///
+/// Without [`crate::ClientBuilder::auto_extend`] the leases are instead only watched
+/// to mark them lost once expired.
+pub(crate) fn start_periodically_extending(client: Client, group: &[Lease]) {
+    let states: Vec<_> = group.iter().map(|l| Arc::downgrade(&l.state)).collect();
+    if !client.auto_extend {
+        tokio::spawn(watch_expiry(client, states));
+        return;
+    }
     tokio::spawn(async move {
+        let mut wait = client.extend_period;
         loop {
-            tokio::time::sleep(client.extend_period).await;
-            match key_lease_v.upgrade() {
-                Some(key_lease_v) => {
-                    let mut lease_v = key_lease_v.1.lock().await;
-                    let key = key_lease_v.0.clone();
-                    match client.extend_lease(key, *lease_v).await {
-                        Ok(new_lease_v) => *lease_v = new_lease_v,
-                        // stop on error, TODO retries, logs?
-                        Err(_) => break,
+            tokio::time::sleep(wait).await;
+            // stop if the lease has been dropped
+            let Some(states) = states.iter().map(Weak::upgrade).collect::<Option<Vec<_>>>() else {
+                break;
+            };
+            // lock in key order, as in `release_group`
+            let mut versions = Vec::with_capacity(states.len());
+            for state in &states {
+                versions.push(state.version.lock().await);
+            }
+            if states.iter().any(|s| s.released.load(Ordering::Relaxed)) {
+                break;
+            }
+
+            let earliest = versions
+                .iter()
+                .map(|v| **v)
+                .min_by_key(|v| v.expiry)
+                .expect("group is not empty");
+            let remaining = earliest.remaining(&client);
+            if remaining.is_zero() {
+                set_lost(&states, LostReason::Expired);
+                break;
+            }
+
+            let lease_vs: Vec<_> = versions.iter().map(|v| v.lease_v).collect();
+            let extend = extend_with_retry(&client, &states, &lease_vs, &earliest);
+            match tokio::time::timeout(remaining, extend).await {
+                Ok(Ok(new_versions)) => {
+                    for ((version, state), new_version) in
+                        versions.iter_mut().zip(&states).zip(new_versions)
+                    {
+                        **version = new_version;
+                        state.set_extended(new_version);
+                    }
+                    wait = client.extend_period;
+                }
+                Ok(Err(Error::LeaseLost)) => {
+                    set_lost(&states, LostReason::Replaced);
+                    break;
+                }
+                Ok(Err(Error::LeaseRevoked { revoked_by })) => {
+                    for state in &states {
+                        warn!(key = state.key, revoked_by, "lease lost, revoked");
+                        let reason = LostReason::Revoked;
+                        state.status.send_replace(LeaseStatus::Lost { reason });
+                    }
+                    break;
+                }
+                Ok(Err(err)) => {
+                    for state in &states {
+                        warn!(key = state.key, "failed to extend lease: {err}");
+                        state.status.send_replace(LeaseStatus::Extending);
                     }
+                    // retry sooner, before the lease expires
+                    wait = min(client.extend_period, earliest.remaining(&client) / 2);
+                }
+                Err(_) => {
+                    set_lost(&states, LostReason::Expired);
+                    break;
                 }
-                // lease dropped
-                None => break,
             }
         }
     });
 }
 
+/// Marks a group of leases, that are not automatically extended, lost once the earliest
+/// has expired. Stops if dropped, released or otherwise lost.
+async fn watch_expiry(client: Client, states: Vec<Weak<LeaseState>>) {
+    loop {
+        let remaining = {
+            // stop if the lease has been dropped
+            let Some(states) = states.iter().map(Weak::upgrade).collect::<Option<Vec<_>>>() else {
+                break;
+            };
+            let mut versions = Vec::with_capacity(states.len());
+            for state in &states {
+                versions.push(state.version.lock().await);
+            }
+            let done = states.iter().any(|s| {
+                s.released.load(Ordering::Relaxed)
+                    || matches!(*s.status.borrow(), LeaseStatus::Lost { .. })
+            });
+            if done {
+                break;
+            }
+
+            let remaining = versions
+                .iter()
+                .map(|v| v.remaining(&client))
+                .min()
+                .expect("group is not empty");
+            if remaining.is_zero() {
+                set_lost(&states, LostReason::Expired);
+                break;
+            }
+            remaining
+        };
+        tokio::time::sleep(remaining).await;
+    }
+}
+
+fn set_lost(states: &[Arc<LeaseState>], reason: LostReason) {
+    for state in states {
+        state.set_lost(reason);
+    }
+}
+
+/// Extends a group of leases retrying transient errors according to the client's
+/// `extend_retry` policy. Gives up early if the next attempt would be after the
+/// `earliest` expiring lease has expired.
+async fn extend_with_retry(
+    client: &Client,
+    states: &[Arc<LeaseState>],
+    lease_vs: &[Uuid],
+    earliest: &LeaseVersion,
+) -> Result<Vec<LeaseVersion>, Error> {
+    let key = states[0].key.as_str();
+    let policy = &client.extend_retry;
+    let mut attempt = 0;
+    loop {
+        let extend = match states {
+            [state] => client
+                .extend_lease(key, state.kind, lease_vs[0])
+                .await
+                .map(|version| vec![version]),
+            _ => {
+                let leases: Vec<_> = states
+                    .iter()
+                    .map(|s| s.key.as_str())
+                    .zip(lease_vs.iter().copied())
+                    .collect();
+                client.extend_leases(&leases).await
+            }
+        };
+        match extend {
+            Err(err) if err.is_retryable() && attempt + 1 < policy.attempts() => {
+                let backoff = policy.backoff(attempt);
+                if backoff >= earliest.remaining(client) {
+                    return Err(err);
+                }
+                debug!(key, attempt, ?backoff, "retrying lease extension: {err}");
+                attempt += 1;
+                tokio::time::sleep(backoff).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Releases a group of leases, ordered by key, deleting them atomically.
+///
+/// If any lease has been lost the remaining leases are deleted individually.
+pub(crate) async fn release_group(mut group: Vec<Lease>) -> Result<(), Error> {
+    for lease in &mut group {
+        // disable release on drop since we're doing that now
+        lease.release_on_drop = false;
+        lease.release_local();
+    }
+    let Some(client) = group.first().map(|l| l.client.clone()) else {
+        return Ok(());
+    };
+
+    // hold v-locks during deletion to ensure no race with extension
+    let mut versions = Vec::with_capacity(group.len());
+    for lease in &group {
+        versions.push(lease.state.version.lock().await);
+        lease.state.released.store(true, Ordering::Relaxed);
+    }
+    let leases: Vec<_> = group
+        .iter()
+        .zip(&versions)
+        .map(|(l, v)| (l.state.key.as_str(), v.lease_v))
+        .collect();
+
+    match client.delete_leases(&leases).await {
+        Err(err) if err.is_lease_lost() => {
+            for (key, lease_v) in leases {
+                match client
+                    .delete_lease(key, LeaseKind::Exclusive, lease_v)
+                    .await
+                {
+                    Err(err) if err.is_lease_lost() => {}
+                    Err(err) => warn!(key, "failed to release lease: {err}"),
+                    Ok(_) => {}
+                }
+            }
+            Err(err)
+        }
+        result => result,
+    }
+}
+
 impl Drop for Lease {
     /// Asynchronously releases the underlying lock.
     fn drop(&mut self) {
@@ -97,14 +667,11 @@ impl Drop for Lease {
             // Clone necessary data before moving self into the spawned task
             let lease = Lease {
                 client: self.client.clone(),
-                key_lease_v: Arc::clone(&self.key_lease_v),
+                state: Arc::clone(&self.state),
                 local_guard: self.local_guard.take(), // Take ownership of the guard
                 release_on_drop: false,
             };
-            tokio::spawn(async move {
-                // TODO retries, logs?
-                _ = lease.release().await;
-            });
+            tokio::spawn(lease.release_after_drop());
         }
     }
 }
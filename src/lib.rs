@@ -3,7 +3,7 @@
 //! # Example
 //! ```
 //! # use std::time::Duration;
-//! # async fn foo() -> anyhow::Result<()> {
+//! # async fn foo() -> Result<(), dynamodb_lease::Error> {
 //! # let dynamodb_client: aws_sdk_dynamodb::Client = unimplemented!();
 //! let client = dynamodb_lease::Client::builder()
 //!     .table_name("example-leases")
@@ -16,6 +16,7 @@
 //! let lease = client.acquire("important-job-123").await?;
 //!
 //! // `lease` periodically extends itself in a background tokio task
+//! // `lease.lost()` resolves if that fails and the lease is no longer held
 //!
 //! // until dropped others will not be able to acquire this lease
 //! assert!(client.try_acquire("important-job-123").await?.is_none());
@@ -25,11 +26,37 @@
 //! # Ok(()) }
 //! ```
 
+mod acquire;
 mod builder;
 mod client;
+mod clock;
+mod dynamodb;
+mod error;
+mod handoff;
+mod leader;
 mod lease;
 mod local;
+#[cfg(feature = "memory")]
+mod memory;
+mod metrics;
+mod multi;
+mod permit;
+mod queue;
+mod retry;
+mod store;
 
+pub use acquire::{AcquireOptions, AcquireStrategy};
 pub use builder::ClientBuilder;
 pub use client::Client;
-pub use lease::Lease;
+pub use dynamodb::{DynamoDbStore, KeySplit, SplitFn, TableOptions};
+pub use error::Error;
+pub use handoff::{HandoffToken, LeaseIdentity};
+pub use leader::{LeaderElection, Leadership};
+pub use lease::{Lease, LeaseStatus, LostReason};
+#[cfg(feature = "memory")]
+pub use memory::MemoryStore;
+pub use metrics::Metrics;
+pub use multi::MultiLease;
+pub use permit::Permit;
+pub use retry::RetryPolicy;
+pub use store::{LeaseInfo, LeaseStore, ListOptions, NewLease, PutLease};
@@ -0,0 +1,362 @@
+use crate::{
+    Error, LeaseInfo, LeaseStore, ListOptions, NewLease, PutLease, store::RESERVED_KEY_PREFIX,
+};
+use async_trait::async_trait;
+use futures_core::stream::BoxStream;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+use uuid::Uuid;
+
+/// In-memory [`LeaseStore`] for tests & local development.
+///
+/// Implements the same semantics as [`crate::DynamoDbStore`], i.e. expiry, conditional
+/// replacement & versioning, but leases are only shared by clients using the same
+/// store (or its clones).
+///
+/// # Example
+/// ```
+/// # async fn foo() -> Result<(), dynamodb_lease::Error> {
+/// let client = dynamodb_lease::Client::builder()
+///     .build_with_store(dynamodb_lease::MemoryStore::default())?;
+///
+/// let lease = client.acquire("important-job-123").await?;
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore(Arc<Mutex<MemoryTable>>);
+
+#[derive(Debug, Default)]
+struct MemoryTable {
+    items: HashMap<String, MemoryItem>,
+    /// Last generation of each key, retained after deletion.
+    generations: HashMap<String, u64>,
+    /// Fair queue tickets of each key & their expiry.
+    queues: HashMap<String, BTreeMap<String, i64>>,
+}
+
+#[derive(Debug)]
+struct MemoryItem {
+    lease_v: Uuid,
+    expiry: i64,
+    fencing_token: u64,
+    owner: String,
+    attributes: BTreeMap<String, String>,
+    revoked_by: Option<String>,
+    /// Expiry of each shared holder, `None` for exclusive leases.
+    readers: Option<HashMap<Uuid, i64>>,
+}
+
+impl MemoryItem {
+    fn info(&self, key: &str) -> LeaseInfo {
+        LeaseInfo {
+            owner: Some(self.owner.clone()),
+            attributes: self.attributes.clone(),
+            revoked_by: self.revoked_by.clone(),
+            ..LeaseInfo::new(key, self.lease_v, self.expiry, self.fencing_token)
+        }
+    }
+}
+
+impl MemoryTable {
+    /// Returns the item for `key` if it has version `lease_v`.
+    fn item_mut(&mut self, key: &str, lease_v: Uuid) -> Result<&mut MemoryItem, Error> {
+        match self.items.get_mut(key) {
+            Some(item) if item.lease_v == lease_v => Ok(item),
+            item => Err(lost_error(item.as_deref())),
+        }
+    }
+
+    /// Returns the item for `key` if it has a shared holder with version `lease_v`.
+    fn shared_item_mut(&mut self, key: &str, lease_v: Uuid) -> Result<&mut MemoryItem, Error> {
+        match self.items.get_mut(key) {
+            Some(item)
+                if item
+                    .readers
+                    .as_ref()
+                    .is_some_and(|r| r.contains_key(&lease_v)) =>
+            {
+                Ok(item)
+            }
+            item => Err(lost_error(item.as_deref())),
+        }
+    }
+
+    /// Returns the next fencing token for `key`.
+    fn next_generation(&mut self, key: &str) -> u64 {
+        let generation = self.generations.entry(key.into()).or_default();
+        *generation += 1;
+        *generation
+    }
+}
+
+/// Returns [`Error::LeaseRevoked`] if the item has been revoked, otherwise [`Error::LeaseLost`].
+fn lost_error(item: Option<&MemoryItem>) -> Error {
+    match item.and_then(|item| item.revoked_by.clone()) {
+        Some(revoked_by) => Error::LeaseRevoked { revoked_by },
+        None => Error::LeaseLost,
+    }
+}
+
+/// Returns [`Error::InvalidKey`] if lease `key` is reserved for internal items, as in
+/// [`crate::DynamoDbStore`], even though this store keeps internal state separately.
+fn check_key(key: &str) -> Result<(), Error> {
+    match key.starts_with(RESERVED_KEY_PREFIX) {
+        true => Err(Error::InvalidKey(key.into())),
+        false => Ok(()),
+    }
+}
+
+#[async_trait]
+impl LeaseStore for MemoryStore {
+    async fn put_lease(&self, lease: NewLease) -> Result<PutLease, Error> {
+        check_key(&lease.key)?;
+        let mut table = self.0.lock().unwrap();
+        if let Some(item) = table.items.get(&lease.key)
+            && item.expiry >= lease.now
+        {
+            let holder = Some(item.info(&lease.key));
+            return Ok(PutLease::Held { holder });
+        }
+
+        let fencing_token = table.next_generation(&lease.key);
+        table.items.insert(
+            lease.key,
+            MemoryItem {
+                lease_v: lease.lease_v,
+                expiry: lease.expiry,
+                fencing_token,
+                owner: lease.owner,
+                attributes: lease.attributes,
+                revoked_by: None,
+                readers: None,
+            },
+        );
+        Ok(PutLease::Acquired { fencing_token })
+    }
+
+    async fn put_leases(&self, leases: Vec<NewLease>) -> Result<Option<Vec<u64>>, Error> {
+        for lease in &leases {
+            check_key(&lease.key)?;
+        }
+        let mut table = self.0.lock().unwrap();
+        let held = leases.iter().any(|lease| {
+            table
+                .items
+                .get(&lease.key)
+                .is_some_and(|item| item.expiry >= lease.now)
+        });
+        if held {
+            return Ok(None);
+        }
+
+        let mut fencing_tokens = Vec::with_capacity(leases.len());
+        for lease in leases {
+            let fencing_token = table.next_generation(&lease.key);
+            fencing_tokens.push(fencing_token);
+            table.items.insert(
+                lease.key,
+                MemoryItem {
+                    lease_v: lease.lease_v,
+                    expiry: lease.expiry,
+                    fencing_token,
+                    owner: lease.owner,
+                    attributes: lease.attributes,
+                    revoked_by: None,
+                    readers: None,
+                },
+            );
+        }
+        Ok(Some(fencing_tokens))
+    }
+
+    async fn extend_leases(&self, leases: &[(&str, Uuid, Uuid)], expiry: i64) -> Result<(), Error> {
+        let mut table = self.0.lock().unwrap();
+        for (key, lease_v, _) in leases {
+            table.item_mut(key, *lease_v)?;
+        }
+        for (key, lease_v, new_lease_v) in leases {
+            let item = table.item_mut(key, *lease_v)?;
+            item.lease_v = *new_lease_v;
+            item.expiry = expiry;
+        }
+        Ok(())
+    }
+
+    async fn delete_leases(&self, leases: &[(&str, Uuid)]) -> Result<(), Error> {
+        let mut table = self.0.lock().unwrap();
+        for (key, lease_v) in leases {
+            table.item_mut(key, *lease_v)?;
+        }
+        for (key, _) in leases {
+            table.items.remove(*key);
+        }
+        Ok(())
+    }
+
+    async fn put_shared_lease(&self, lease: NewLease) -> Result<Option<u64>, Error> {
+        check_key(&lease.key)?;
+        let mut table = self.0.lock().unwrap();
+        match table.items.get_mut(&lease.key) {
+            Some(item) if item.readers.is_some() => {
+                let readers = item.readers.get_or_insert_default();
+                readers.retain(|_, expiry| *expiry >= lease.now);
+                readers.insert(lease.lease_v, lease.expiry);
+                item.expiry = item.expiry.max(lease.expiry);
+                Ok(Some(item.fencing_token))
+            }
+            Some(item) if item.expiry >= lease.now => Ok(None),
+            _ => {
+                let fencing_token = table.next_generation(&lease.key);
+                table.items.insert(
+                    lease.key,
+                    MemoryItem {
+                        lease_v: lease.lease_v,
+                        expiry: lease.expiry,
+                        fencing_token,
+                        owner: lease.owner,
+                        attributes: lease.attributes,
+                        revoked_by: None,
+                        readers: Some([(lease.lease_v, lease.expiry)].into()),
+                    },
+                );
+                Ok(Some(fencing_token))
+            }
+        }
+    }
+
+    async fn put_ticket(
+        &self,
+        key: &str,
+        ticket: &str,
+        expiry: i64,
+    ) -> Result<BTreeMap<String, i64>, Error> {
+        let mut table = self.0.lock().unwrap();
+        let queue = table.queues.entry(key.into()).or_default();
+        queue.insert(ticket.into(), expiry);
+        Ok(queue.clone())
+    }
+
+    async fn delete_tickets(&self, key: &str, tickets: &[&str]) -> Result<(), Error> {
+        let mut table = self.0.lock().unwrap();
+        if let Some(queue) = table.queues.get_mut(key) {
+            for ticket in tickets {
+                queue.remove(*ticket);
+            }
+            if queue.is_empty() {
+                table.queues.remove(key);
+            }
+        }
+        Ok(())
+    }
+
+    async fn extend_shared_lease(
+        &self,
+        key: &str,
+        lease_v: Uuid,
+        new_lease_v: Uuid,
+        expiry: i64,
+        now: i64,
+    ) -> Result<(), Error> {
+        let mut table = self.0.lock().unwrap();
+        let item = table.shared_item_mut(key, lease_v)?;
+        let readers = item.readers.get_or_insert_default();
+        readers.retain(|v, expiry| *v != lease_v && *expiry >= now);
+        readers.insert(new_lease_v, expiry);
+        item.expiry = item.expiry.max(expiry);
+        Ok(())
+    }
+
+    async fn delete_shared_lease(&self, key: &str, lease_v: Uuid) -> Result<(), Error> {
+        let mut table = self.0.lock().unwrap();
+        let item = table.shared_item_mut(key, lease_v)?;
+        let readers = item.readers.get_or_insert_default();
+        readers.remove(&lease_v);
+        if readers.is_empty() {
+            table.items.remove(key);
+        }
+        Ok(())
+    }
+
+    async fn extend_lease(
+        &self,
+        key: &str,
+        lease_v: Uuid,
+        new_lease_v: Uuid,
+        expiry: i64,
+    ) -> Result<(), Error> {
+        let mut table = self.0.lock().unwrap();
+        let item = table.item_mut(key, lease_v)?;
+        item.lease_v = new_lease_v;
+        item.expiry = expiry;
+        Ok(())
+    }
+
+    async fn adopt_lease(
+        &self,
+        key: &str,
+        lease_v: Uuid,
+        new_lease_v: Uuid,
+        expiry: i64,
+        owner: &str,
+    ) -> Result<(), Error> {
+        let mut table = self.0.lock().unwrap();
+        let item = table.item_mut(key, lease_v)?;
+        item.lease_v = new_lease_v;
+        item.expiry = expiry;
+        item.owner = owner.into();
+        Ok(())
+    }
+
+    async fn delete_lease(&self, key: &str, lease_v: Uuid) -> Result<(), Error> {
+        let mut table = self.0.lock().unwrap();
+        table.item_mut(key, lease_v)?;
+        table.items.remove(key);
+        Ok(())
+    }
+
+    async fn revoke_lease(
+        &self,
+        key: &str,
+        lease_v: Option<Uuid>,
+        revoked_by: &str,
+        expiry: i64,
+    ) -> Result<(), Error> {
+        check_key(key)?;
+        let mut table = self.0.lock().unwrap();
+        let item = table
+            .items
+            .get_mut(key)
+            .filter(|item| lease_v.is_none_or(|v| item.lease_v == v))
+            .ok_or(Error::LeaseLost)?;
+        item.lease_v = Uuid::new_v4();
+        item.expiry = expiry;
+        item.revoked_by = Some(revoked_by.into());
+        item.readers = None;
+        Ok(())
+    }
+
+    async fn get_lease(&self, key: &str) -> Result<Option<LeaseInfo>, Error> {
+        let table = self.0.lock().unwrap();
+        Ok(table.items.get(key).map(|item| item.info(key)))
+    }
+
+    /// Lists a snapshot of current leases ordered by key.
+    fn list_leases(&self, options: &ListOptions) -> BoxStream<'static, Result<LeaseInfo, Error>> {
+        let table = self.0.lock().unwrap();
+        let mut leases: Vec<_> = table
+            .items
+            .iter()
+            .filter(|(key, _)| options.prefix.as_ref().is_none_or(|p| key.starts_with(p)))
+            .map(|(key, item)| item.info(key))
+            .filter(|info| !(options.hide_expired && info.is_expired()))
+            .collect();
+        leases.sort_by(|a, b| a.key.cmp(&b.key));
+        Box::pin(futures_util::stream::iter(leases.into_iter().map(Ok)))
+    }
+
+    async fn check_schema(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Snapshot of [`crate::Client`] counters, see [`crate::Client::metrics`].
+///
+/// Counters are shared by a client & its clones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Metrics {
+    /// Leases successfully released after being dropped.
+    pub drop_releases: u64,
+    /// Retried attempts to release dropped leases.
+    pub drop_release_retries: u64,
+    /// Dropped leases that could not be released. These will block other
+    /// acquisitions until they expire.
+    pub leaked_leases: u64,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    drop_releases: AtomicU64,
+    drop_release_retries: AtomicU64,
+    leaked_leases: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn drop_released(&self) {
+        self.drop_releases.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn drop_release_retried(&self) {
+        self.drop_release_retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn leaked(&self) {
+        self.leaked_leases.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> Metrics {
+        Metrics {
+            drop_releases: self.drop_releases.load(Ordering::Relaxed),
+            drop_release_retries: self.drop_release_retries.load(Ordering::Relaxed),
+            leaked_leases: self.leaked_leases.load(Ordering::Relaxed),
+        }
+    }
+}
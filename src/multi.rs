@@ -0,0 +1,56 @@
+use crate::{Error, Lease, LeaseStatus, LostReason, lease};
+use tokio::sync::watch;
+
+/// Represents held distributed leases of multiple keys, acquired atomically,
+/// see [`crate::Client::acquire_all`].
+///
+/// All leases are extended together in a single background task, atomically in a
+/// single db transaction. If any extension fails all leases observe the same status.
+///
+/// On drop asynchronously releases each of the underlying leases.
+#[derive(Debug)]
+pub struct MultiLease {
+    /// Leases ordered by key.
+    leases: Vec<Lease>,
+}
+
+impl MultiLease {
+    pub(crate) fn new(leases: Vec<Lease>) -> Self {
+        Self { leases }
+    }
+
+    /// Returns the held leases, ordered by key.
+    pub fn leases(&self) -> &[Lease] {
+        &self.leases
+    }
+
+    /// Returns the held lease of the given `key`, if any.
+    pub fn lease(&self, key: &str) -> Option<&Lease> {
+        self.leases.iter().find(|l| l.key() == key)
+    }
+
+    /// Returns the current status of these leases, see [`Lease::status`].
+    pub fn status(&self) -> LeaseStatus {
+        self.leases[0].status()
+    }
+
+    /// Returns a receiver that observes each [`LeaseStatus`] change, see [`Lease::watch_status`].
+    pub fn watch_status(&self) -> watch::Receiver<LeaseStatus> {
+        self.leases[0].watch_status()
+    }
+
+    /// Waits until these leases are lost, returning why, see [`Lease::lost`].
+    pub async fn lost(&self) -> LostReason {
+        self.leases[0].lost().await
+    }
+
+    /// Releases all leases returning `Ok(())` after successful deletion in a single
+    /// db transaction, see [`Lease::release`].
+    ///
+    /// # Errors
+    /// Returns [`Error::LeaseLost`] or [`Error::LeaseRevoked`] if any lease had already been
+    /// lost. Other leases are still released.
+    pub async fn release(self) -> Result<(), Error> {
+        lease::release_group(self.leases).await
+    }
+}
@@ -0,0 +1,58 @@
+use crate::{Error, Lease, LeaseStatus, LostReason};
+use tokio::sync::watch;
+
+/// Represents a held permit of a distributed counting semaphore, see [`crate::Client::acquire_permit`].
+///
+/// Each permit is a [`Lease`] of one of the semaphore's slot keys, so is extended in
+/// the background & released on drop in the same way.
+#[derive(Debug)]
+pub struct Permit {
+    lease: Lease,
+    slot: u32,
+}
+
+impl Permit {
+    pub(crate) fn new(lease: Lease, slot: u32) -> Self {
+        Self { lease, slot }
+    }
+
+    /// Returns the slot held by this permit, from `0` to `max_permits - 1`.
+    pub fn slot(&self) -> u32 {
+        self.slot
+    }
+
+    /// Returns the underlying lease of this permit's slot key.
+    pub fn lease(&self) -> &Lease {
+        &self.lease
+    }
+
+    /// Returns the fencing token of this permit's slot lease, see [`Lease::fencing_token`].
+    pub fn fencing_token(&self) -> u64 {
+        self.lease.fencing_token()
+    }
+
+    /// Returns the current status of this permit, see [`Lease::status`].
+    pub fn status(&self) -> LeaseStatus {
+        self.lease.status()
+    }
+
+    /// Returns a receiver that observes each [`LeaseStatus`] change, see [`Lease::watch_status`].
+    pub fn watch_status(&self) -> watch::Receiver<LeaseStatus> {
+        self.lease.watch_status()
+    }
+
+    /// Waits until this permit is lost, returning why, see [`Lease::lost`].
+    pub async fn lost(&self) -> LostReason {
+        self.lease.lost().await
+    }
+
+    /// Releases the permit returning `Ok(())` after successful deletion, see [`Lease::release`].
+    pub async fn release(self) -> Result<(), Error> {
+        self.lease.release().await
+    }
+}
+
+/// Returns the lease key for a semaphore `slot`.
+pub(crate) fn slot_key(name: &str, slot: u32) -> String {
+    format!("{name}/permit/{slot}")
+}
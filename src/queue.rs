@@ -0,0 +1,78 @@
+use crate::{Client, Error};
+use std::time::Duration;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// A waiter's ticket in the fair queue of a key, see [`crate::ClientBuilder::fair_acquire`].
+///
+/// On drop asynchronously removes itself from the queue.
+#[derive(Debug)]
+pub(crate) struct Ticket {
+    client: Client,
+    /// The lease key.
+    key: String,
+    /// Ordered by creation time, then randomly.
+    id: String,
+}
+
+impl Ticket {
+    pub(crate) fn new(client: Client, key: &str) -> Self {
+        let now_ms = client.clock.now().unix_timestamp_nanos() / 1_000_000;
+        Self {
+            client,
+            key: key.into(),
+            id: format!("{now_ms:020}-{}", Uuid::new_v4().simple()),
+        }
+    }
+
+    /// Returns the max wait between refreshes, half the ticket's ttl, so a waiter
+    /// refreshes its ticket before it expires regardless of the acquire cooldown.
+    pub(crate) fn refresh_period(&self) -> Duration {
+        Duration::from_secs(self.client.lease_ttl_seconds.into()) / 2
+    }
+
+    /// Puts or refreshes this ticket, returning `true` if it is the oldest live ticket
+    /// in the queue, i.e. it is this waiter's turn to acquire.
+    ///
+    /// Expired tickets are removed from the queue.
+    pub(crate) async fn refresh(&self) -> Result<bool, Error> {
+        let now = self.client.clock.now().unix_timestamp();
+        let expiry = now + i64::from(self.client.lease_ttl_seconds);
+        let tickets = self
+            .client
+            .store
+            .put_ticket(&self.key, &self.id, expiry)
+            .await?;
+
+        let expired: Vec<_> = tickets
+            .iter()
+            .filter(|(_, expiry)| **expiry < now)
+            .map(|(id, _)| id.as_str())
+            .collect();
+        if !expired.is_empty() {
+            debug!(key = self.key, "removing expired tickets");
+            if let Err(err) = self.client.store.delete_tickets(&self.key, &expired).await {
+                warn!(key = self.key, "failed to remove expired tickets: {err}");
+            }
+        }
+
+        let first = tickets
+            .iter()
+            .find(|(_, expiry)| **expiry >= now)
+            .map(|(id, _)| id);
+        Ok(first == Some(&self.id))
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        let client = self.client.clone();
+        let key = std::mem::take(&mut self.key);
+        let id = std::mem::take(&mut self.id);
+        tokio::spawn(async move {
+            if let Err(err) = client.store.delete_tickets(&key, &[&id]).await {
+                warn!(key, "failed to remove ticket, it will expire: {err}");
+            }
+        });
+    }
+}
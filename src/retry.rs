@@ -0,0 +1,169 @@
+use aws_sdk_dynamodb::error::{ProvideErrorMetadata, SdkError};
+use aws_smithy_runtime_api::client::orchestrator;
+use std::{cmp::min, time::Duration};
+
+/// Service error codes that indicate a transient failure.
+const TRANSIENT_ERROR_CODES: &[&str] = &[
+    "ThrottlingException",
+    "ProvisionedThroughputExceededException",
+    "RequestLimitExceeded",
+    "InternalServerError",
+    "ServiceUnavailable",
+];
+
+/// Policy for retrying transient db failures with exponential backoff & jitter.
+///
+/// Retries are always bounded by the lease expiry, i.e. no attempt will be made
+/// after the lease would have expired regardless of the remaining attempts.
+///
+/// # Example
+/// ```
+/// # use std::time::Duration;
+/// let policy = dynamodb_lease::RetryPolicy::default()
+///     .max_attempts(10)
+///     .initial_backoff(Duration::from_millis(50))
+///     .max_backoff(Duration::from_secs(2));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns a policy that makes a single attempt without retrying.
+    pub fn no_retry() -> Self {
+        Self::default().max_attempts(1)
+    }
+
+    /// Sets the max number of attempts, including the initial attempt.
+    ///
+    /// Default `5`.
+    ///
+    /// # Panics
+    /// Panics if zero.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        assert!(max_attempts > 0, "must be greater than zero");
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Sets the backoff before the first retry. Each subsequent retry doubles
+    /// the backoff up to [`RetryPolicy::max_backoff`].
+    ///
+    /// Default `100ms`.
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Sets the max backoff between retries.
+    ///
+    /// Default `5s`.
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Sets whether to randomise backoffs, to avoid many clients retrying in lockstep.
+    /// When enabled each backoff is a random duration between half & all of the
+    /// exponential backoff.
+    ///
+    /// Default `true`.
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Returns the max number of attempts, including the initial attempt.
+    pub(crate) fn attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Returns the backoff to wait after a failed `attempt` (starting at `0`).
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self
+            .initial_backoff
+            .saturating_mul(2_u32.saturating_pow(attempt));
+        let backoff = min(exp, self.max_backoff);
+        match self.jitter {
+            true => backoff / 2 + backoff.mul_f64(fastrand::f64() / 2.0),
+            false => backoff,
+        }
+    }
+}
+
+/// Returns `true` if the error is likely transient, e.g. throttling, 5xx responses
+/// or timeouts, so may succeed if retried.
+pub(crate) fn is_transient<E: ProvideErrorMetadata>(
+    err: &SdkError<E, orchestrator::HttpResponse>,
+) -> bool {
+    match err {
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) | SdkError::ResponseError(_) => {
+            true
+        }
+        SdkError::ServiceError(se) => {
+            se.raw().status().is_server_error()
+                || se
+                    .err()
+                    .code()
+                    .is_some_and(|code| TRANSIENT_ERROR_CODES.contains(&code))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn backoff_bounds() {
+        let policy = RetryPolicy::default()
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_secs(1));
+        for attempt in 0..40 {
+            let max = min(
+                Duration::from_millis(100) * 2_u32.pow(attempt.min(10)),
+                policy.max_backoff,
+            );
+            for _ in 0..20 {
+                let backoff = policy.backoff(attempt);
+                assert!(backoff >= max / 2, "attempt {attempt}: {backoff:?}");
+                assert!(backoff <= max, "attempt {attempt}: {backoff:?}");
+            }
+        }
+
+        let policy = policy.jitter(false);
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+        assert_eq!(policy.backoff(4), Duration::from_secs(1));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn max_attempts() {
+        assert_eq!(RetryPolicy::default().attempts(), 5);
+        assert_eq!(RetryPolicy::default().max_attempts(3).attempts(), 3);
+        assert_eq!(RetryPolicy::no_retry().attempts(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "must be greater than zero")]
+    fn zero_max_attempts() {
+        RetryPolicy::default().max_attempts(0);
+    }
+}
@@ -0,0 +1,289 @@
+use crate::{Error, TableOptions};
+use async_trait::async_trait;
+use futures_core::stream::BoxStream;
+use std::{collections::BTreeMap, fmt};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// Prefix of keys reserved for internal items, e.g. fair queues, that lease keys must not use.
+pub(crate) const RESERVED_KEY_PREFIX: &str = "~lease/";
+
+/// Storage backend for leases used by [`crate::Client`].
+///
+/// [`crate::DynamoDbStore`] is the default implementation. With the `memory` feature
+/// an in-memory implementation is also available for tests & local development.
+///
+/// Implementations must make each operation atomic, i.e. conditions must be checked
+/// & applied without races with other concurrent operations on the same key.
+/// Timestamps are unix timestamps in seconds & are provided by the client.
+#[async_trait]
+pub trait LeaseStore: fmt::Debug + Send + Sync + 'static {
+    /// Puts a new lease if no lease exists for the key or the existing lease has
+    /// expired, i.e. its expiry is before [`NewLease::now`].
+    ///
+    /// Returns the lease fencing token, which must be greater than the token of any previous
+    /// lease for the same key. Returns [`PutLease::Held`] if an active lease already exists,
+    /// with its current state if available.
+    /// Contention that prevents an answer, e.g. concurrent token increments, should return
+    /// a retryable [`Error`] rather than [`PutLease::Held`].
+    ///
+    /// Returns [`Error::InvalidKey`] if the key is reserved for internal items, i.e. starts
+    /// with `~lease/`. Likewise for other puts & [`LeaseStore::revoke_lease`].
+    async fn put_lease(&self, lease: NewLease) -> Result<PutLease, Error>;
+
+    /// Extends a lease if it still has version `lease_v`, setting `new_lease_v` & `expiry`.
+    ///
+    /// Returns [`Error::LeaseRevoked`] if the lease has been revoked, see
+    /// [`LeaseStore::revoke_lease`], otherwise [`Error::LeaseLost`] if the lease has been
+    /// deleted or has a different version.
+    async fn extend_lease(
+        &self,
+        key: &str,
+        lease_v: Uuid,
+        new_lease_v: Uuid,
+        expiry: i64,
+    ) -> Result<(), Error>;
+
+    /// Adopts a lease if it still has version `lease_v`, setting `new_lease_v` & `expiry`
+    /// like [`LeaseStore::extend_lease`] & also setting the lease `owner`.
+    ///
+    /// The default implementation extends the lease without setting the owner.
+    async fn adopt_lease(
+        &self,
+        key: &str,
+        lease_v: Uuid,
+        new_lease_v: Uuid,
+        expiry: i64,
+        owner: &str,
+    ) -> Result<(), Error> {
+        let _ = owner;
+        self.extend_lease(key, lease_v, new_lease_v, expiry).await
+    }
+
+    /// Deletes a lease if it still has version `lease_v`.
+    ///
+    /// Returns [`Error::LeaseLost`] if the lease has been deleted or has a different version.
+    async fn delete_lease(&self, key: &str, lease_v: Uuid) -> Result<(), Error>;
+
+    /// Puts new leases for multiple distinct keys atomically, i.e. either all leases are
+    /// put or none are, with the same conditions as [`LeaseStore::put_lease`].
+    ///
+    /// Returns the fencing token of each lease, in order. Returns `Ok(None)` if an active
+    /// lease already exists for any key.
+    async fn put_leases(&self, leases: Vec<NewLease>) -> Result<Option<Vec<u64>>, Error>;
+
+    /// Extends leases of multiple keys atomically, if all still have their versions.
+    /// `leases` are `(key, lease_v, new_lease_v)`.
+    ///
+    /// Returns errors like [`LeaseStore::extend_lease`] if any lease cannot be extended,
+    /// in which case none are extended.
+    async fn extend_leases(&self, leases: &[(&str, Uuid, Uuid)], expiry: i64) -> Result<(), Error>;
+
+    /// Deletes leases of multiple keys atomically, if all still have their versions.
+    /// `leases` are `(key, lease_v)`.
+    ///
+    /// Returns errors like [`LeaseStore::delete_lease`] if any lease cannot be deleted,
+    /// in which case none are deleted.
+    async fn delete_leases(&self, leases: &[(&str, Uuid)]) -> Result<(), Error>;
+
+    /// Puts or refreshes a waiter `ticket` with `expiry` in the fair queue of lease `key`,
+    /// creating the queue if necessary, see [`crate::ClientBuilder::fair_acquire`].
+    ///
+    /// Returns all tickets of the queue & their expiry, including expired tickets.
+    async fn put_ticket(
+        &self,
+        key: &str,
+        ticket: &str,
+        expiry: i64,
+    ) -> Result<BTreeMap<String, i64>, Error>;
+
+    /// Removes `tickets`, if present, from the fair queue of lease `key`. Deletes the queue
+    /// if no tickets remain.
+    async fn delete_tickets(&self, key: &str, tickets: &[&str]) -> Result<(), Error>;
+
+    /// Puts a new shared lease, with [`NewLease::lease_v`] identifying the shared holder.
+    ///
+    /// Shared leases of the same key may be held concurrently, but not while an exclusive
+    /// lease, see [`LeaseStore::put_lease`], is held. Likewise an exclusive lease must not
+    /// be put while any shared lease of the key is held. Each shared holder expires
+    /// independently.
+    ///
+    /// Returns the lease fencing token, which is shared by concurrent shared holders.
+    /// Returns `Ok(None)` if an active exclusive lease already exists.
+    async fn put_shared_lease(&self, lease: NewLease) -> Result<Option<u64>, Error>;
+
+    /// Extends a shared lease if it is still held with version `lease_v`, replacing it
+    /// with `new_lease_v` & `expiry`.
+    ///
+    /// Other shared holders that expired before `now`, the client's current unix timestamp,
+    /// may be removed.
+    ///
+    /// Returns errors like [`LeaseStore::extend_lease`].
+    async fn extend_shared_lease(
+        &self,
+        key: &str,
+        lease_v: Uuid,
+        new_lease_v: Uuid,
+        expiry: i64,
+        now: i64,
+    ) -> Result<(), Error>;
+
+    /// Deletes a shared lease if it is still held with version `lease_v`.
+    ///
+    /// Returns errors like [`LeaseStore::delete_lease`].
+    async fn delete_shared_lease(&self, key: &str, lease_v: Uuid) -> Result<(), Error>;
+
+    /// Revokes the lease for the key regardless of holder, if it has version `lease_v`
+    /// or for any version if `None`. The lease must be updated to a new version with the
+    /// given past `expiry`, so it may be immediately acquired, & record `revoked_by`
+    /// until replaced. All shared holders are also revoked.
+    ///
+    /// Returns [`Error::LeaseLost`] if no lease exists or it has a different version.
+    async fn revoke_lease(
+        &self,
+        key: &str,
+        lease_v: Option<Uuid>,
+        revoked_by: &str,
+        expiry: i64,
+    ) -> Result<(), Error>;
+
+    /// Returns the lease for the key, if any, including leases that have expired but
+    /// not yet been deleted.
+    ///
+    /// Reads must be strongly consistent, i.e. reflect all previously completed writes.
+    async fn get_lease(&self, key: &str) -> Result<Option<LeaseInfo>, Error>;
+
+    /// Returns a stream of all leases matching `options`, fetching pages as required.
+    ///
+    /// Leases that have expired but not yet been deleted are included unless
+    /// [`ListOptions::hide_expired`] is set.
+    fn list_leases(&self, options: &ListOptions) -> BoxStream<'static, Result<LeaseInfo, Error>>;
+
+    /// Checks the store is ready to use, e.g. a db table exists with a valid schema.
+    async fn check_schema(&self) -> Result<(), Error>;
+
+    /// Creates or updates storage, e.g. a db table, so that it is ready to use.
+    /// Must succeed if storage already exists.
+    ///
+    /// The default implementation does nothing, for stores that need no setup.
+    async fn create_table(&self, options: &TableOptions) -> Result<(), Error> {
+        _ = options;
+        Ok(())
+    }
+}
+
+/// Options for listing leases, see [`crate::Client::list_leases`].
+///
+/// # Example
+/// ```
+/// let options = dynamodb_lease::ListOptions::default()
+///     .prefix("job/")
+///     .hide_expired(true);
+/// ```
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct ListOptions {
+    /// Only list leases with keys starting with this prefix.
+    pub prefix: Option<String>,
+    /// Don't list leases that have expired, according to the local clock, but have
+    /// not yet been deleted.
+    pub hide_expired: bool,
+}
+
+impl ListOptions {
+    /// Only list leases with keys starting with `prefix`.
+    ///
+    /// Default none, i.e. list all leases.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Sets whether to omit leases that have expired but are still pending deletion
+    /// by dynamodb time to live, which may take some time.
+    ///
+    /// Default `false`.
+    pub fn hide_expired(mut self, hide_expired: bool) -> Self {
+        self.hide_expired = hide_expired;
+        self
+    }
+}
+
+/// Outcome of [`LeaseStore::put_lease`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PutLease {
+    /// The lease was put.
+    Acquired { fencing_token: u64 },
+    /// An active lease already exists.
+    Held {
+        /// The existing lease, if returned by the store.
+        holder: Option<LeaseInfo>,
+    },
+}
+
+/// A new lease to put into a [`LeaseStore`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct NewLease {
+    pub key: String,
+    /// Version of the new lease.
+    pub lease_v: Uuid,
+    /// Unix timestamp the new lease expires.
+    pub expiry: i64,
+    /// Current unix timestamp. Existing leases that expire before this may be replaced.
+    pub now: i64,
+    /// Identity of the lease holder, see [`crate::ClientBuilder::owner`].
+    pub owner: String,
+    /// Additional attributes to store with the lease, see [`crate::ClientBuilder::lease_attribute`].
+    pub attributes: BTreeMap<String, String>,
+}
+
+/// A lease as currently stored, see [`crate::Client::describe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LeaseInfo {
+    pub key: String,
+    /// Current version of the lease, this changes each time the lease is extended.
+    pub lease_v: Uuid,
+    /// Unix timestamp the lease expires, unless extended.
+    pub expiry: i64,
+    /// Fencing token of the current holder, see [`crate::Lease::fencing_token`].
+    pub fencing_token: u64,
+    /// Identity of the lease holder, see [`crate::ClientBuilder::owner`].
+    /// `None` for leases written by clients that did not store an owner.
+    pub owner: Option<String>,
+    /// Additional attributes stored with the lease, see [`crate::ClientBuilder::lease_attribute`].
+    pub attributes: BTreeMap<String, String>,
+    /// Owner of the client that revoked the lease, see [`crate::Client::force_release`].
+    /// `None` unless revoked & not yet re-acquired.
+    pub revoked_by: Option<String>,
+}
+
+impl LeaseInfo {
+    /// Returns a new [`LeaseInfo`] with no owner or attributes.
+    pub fn new(key: impl Into<String>, lease_v: Uuid, expiry: i64, fencing_token: u64) -> Self {
+        Self {
+            key: key.into(),
+            lease_v,
+            expiry,
+            fencing_token,
+            owner: None,
+            attributes: <_>::default(),
+            revoked_by: None,
+        }
+    }
+
+    /// Returns the time the lease expires, unless extended.
+    pub fn expires_at(&self) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(self.expiry).unwrap_or(OffsetDateTime::UNIX_EPOCH)
+    }
+
+    /// Returns `true` if the lease has expired, according to the local clock, so may be
+    /// acquired by another client.
+    ///
+    /// Expired leases may remain in dynamodb for some time until deleted by time to live.
+    pub fn is_expired(&self) -> bool {
+        self.expiry < OffsetDateTime::now_utc().unix_timestamp()
+    }
+}
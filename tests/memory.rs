@@ -0,0 +1,1056 @@
+//! Tests using the in-memory store, these don't require dynamodb-local.
+#[allow(dead_code)]
+mod util;
+
+use anyhow::Context;
+use dynamodb_lease::{
+    AcquireOptions, AcquireStrategy, Client, HandoffToken, Leadership, LeaseIdentity, LeaseStatus,
+    ListOptions, LostReason, MemoryStore, RetryPolicy,
+};
+use futures_util::TryStreamExt;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use util::{
+    faulty::{Fault, FaultyStore, Op},
+    *,
+};
+
+#[tokio::test]
+async fn try_acquire() {
+    let store = MemoryStore::default();
+    let client = Client::builder().build_with_store(store.clone()).unwrap();
+    // use 2 clients to avoid local locking / simulate distributed usage
+    let client2 = Client::builder().build_with_store(store).unwrap();
+
+    let lease1 = client.try_acquire("try_acquire").await.unwrap();
+    assert!(lease1.is_some());
+
+    // subsequent attempts should fail
+    assert!(client2.try_acquire("try_acquire").await.unwrap().is_none());
+    assert!(client.try_acquire("try_acquire").await.unwrap().is_none());
+
+    // dropping should asynchronously end the lease
+    drop(lease1);
+
+    // in shortish order the key should be acquirable again
+    retry::until_ok(|| async {
+        client2
+            .try_acquire("try_acquire")
+            .await?
+            .context("did not acquire")
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn acquire_timeout() {
+    let store = MemoryStore::default();
+    let client = Client::builder().build_with_store(store.clone()).unwrap();
+    let client2 = Client::builder()
+        .acquire_cooldown(Duration::from_millis(10))
+        .build_with_store(store)
+        .unwrap();
+
+    let lease1 = client.acquire("acquire_timeout").await.unwrap();
+
+    let err = client2
+        .acquire_timeout("acquire_timeout", Duration::from_millis(50))
+        .await
+        .expect_err("should not acquire while lease1 is alive");
+    assert!(err.is_timeout(), "{err:?}");
+
+    drop(lease1);
+
+    client2
+        .acquire_timeout("acquire_timeout", TEST_WAIT)
+        .await
+        .expect("failed to acquire");
+}
+
+#[tokio::test]
+async fn release_fencing_token() {
+    let client = Client::builder()
+        .build_with_store(MemoryStore::default())
+        .unwrap();
+
+    let lease = client.acquire("release_fencing_token").await.unwrap();
+    let token1 = lease.fencing_token();
+    lease.release().await.unwrap();
+
+    // released leases can be immediately acquired with the next token
+    let lease = client
+        .try_acquire("release_fencing_token")
+        .await
+        .unwrap()
+        .expect("should acquire after release");
+    assert_eq!(lease.fencing_token(), token1 + 1);
+}
+
+#[tokio::test]
+async fn internal_item_keys() {
+    let client = Client::builder()
+        .fair_acquire(true)
+        .build_with_store(MemoryStore::default())
+        .unwrap();
+
+    // keys resembling internal items are independent leases
+    for key in ["foo", "foo/generation", "foo/queue", "foo~lease/"] {
+        let lease = client.acquire(key).await.unwrap();
+        assert_eq!(lease.fencing_token(), 1, "{key}");
+        lease.release().await.unwrap();
+        let lease = client.acquire(key).await.unwrap();
+        assert_eq!(lease.fencing_token(), 2, "{key}");
+        lease.release().await.unwrap();
+    }
+
+    let err = client
+        .try_acquire("~lease/generation/foo")
+        .await
+        .expect_err("should reject reserved key");
+    assert!(err.is_invalid_key(), "{err:?}");
+}
+
+#[tokio::test]
+async fn extend_past_ttl() {
+    let store = MemoryStore::default();
+    let client = Client::builder()
+        .lease_ttl_seconds(2)
+        .extend_every(Duration::from_millis(100))
+        .build_with_store(store.clone())
+        .unwrap();
+    let client2 = Client::builder().build_with_store(store).unwrap();
+
+    let lease = client.acquire("extend_past_ttl").await.unwrap();
+    let lease_v = lease.lease_v().await;
+
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert_ne!(
+        lease.lease_v().await,
+        lease_v,
+        "lease should have been extended"
+    );
+    assert!(
+        client2
+            .try_acquire("extend_past_ttl")
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn extend_transient_faults() {
+    let store = FaultyStore::default();
+    let client = Client::builder()
+        .lease_ttl_seconds(3)
+        .extend_every(Duration::from_millis(100))
+        .extend_retry(
+            RetryPolicy::default()
+                .max_attempts(3)
+                .initial_backoff(Duration::from_millis(10)),
+        )
+        .build_with_store(store.clone())
+        .unwrap();
+
+    let lease = client.acquire("extend_transient_faults").await.unwrap();
+    let mut status = lease.watch_status();
+    let seen = Arc::new(Mutex::new(vec![]));
+    tokio::spawn({
+        let seen = seen.clone();
+        let mut status = lease.watch_status();
+        async move {
+            while status.changed().await.is_ok() {
+                seen.lock().unwrap().push(*status.borrow_and_update());
+            }
+        }
+    });
+
+    // faults within the retry policy are retried within a single extension
+    let calls = store.calls(Op::Extend);
+    store.fail(Op::Extend, Fault::Throttle, 2);
+    retry::until_ok(|| async {
+        let retried = store.calls(Op::Extend) >= calls + 4;
+        retried.then_some(()).context("not extended after faults")
+    })
+    .await;
+    assert!(
+        !seen.lock().unwrap().contains(&LeaseStatus::Extending),
+        "{seen:?}"
+    );
+
+    // faults exhausting the retry policy are retried by the next extension
+    store.fail(Op::Extend, Fault::ServerError, 3);
+    tokio::time::timeout(TEST_WAIT, status.wait_for(|s| *s == LeaseStatus::Extending))
+        .await
+        .expect("should be extending after failed extension")
+        .unwrap();
+    tokio::time::timeout(TEST_WAIT, status.wait_for(|s| *s == LeaseStatus::Held))
+        .await
+        .expect("should be held after successful extension")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn extend_faults_outlast_expiry() {
+    let store = FaultyStore::default();
+    let client = Client::builder()
+        .lease_ttl_seconds(2)
+        .extend_every(Duration::from_millis(100))
+        .build_with_store(store.clone())
+        .unwrap();
+
+    let lease = client
+        .acquire("extend_faults_outlast_expiry")
+        .await
+        .unwrap();
+    let mut status = lease.watch_status();
+    store.fail(Op::Extend, Fault::Throttle, usize::MAX);
+
+    tokio::time::timeout(TEST_WAIT, status.wait_for(|s| *s == LeaseStatus::Extending))
+        .await
+        .expect("should be extending after failed extension")
+        .unwrap();
+    let reason = tokio::time::timeout(TEST_WAIT, lease.lost())
+        .await
+        .expect("lease should expire");
+    assert_eq!(reason, LostReason::Expired);
+}
+
+#[tokio::test]
+async fn extend_conditional_failure() {
+    let store = FaultyStore::default();
+    let client = Client::builder()
+        .extend_every(Duration::from_millis(100))
+        .build_with_store(store.clone())
+        .unwrap();
+
+    let lease = client.acquire("extend_conditional_failure").await.unwrap();
+    let calls = store.calls(Op::Extend);
+    store.fail(Op::Extend, Fault::Conditional, 1);
+
+    // lost on the first failure, without retrying or waiting for expiry
+    let reason = tokio::time::timeout(Duration::from_secs(1), lease.lost())
+        .await
+        .expect("lease should be lost");
+    assert_eq!(reason, LostReason::Replaced);
+    assert_eq!(store.calls(Op::Extend), calls + 1);
+}
+
+#[tokio::test]
+async fn drop_release_faults() {
+    let store = FaultyStore::default();
+    let client = Client::builder()
+        .release_retry(
+            RetryPolicy::default()
+                .max_attempts(3)
+                .initial_backoff(Duration::from_millis(10)),
+        )
+        .build_with_store(store.clone())
+        .unwrap();
+    let client2 = Client::builder().build_with_store(store.clone()).unwrap();
+
+    // transient faults are retried within the budget
+    let lease = client.acquire("drop_release_faults").await.unwrap();
+    store.fail(Op::Delete, Fault::Throttle, 2);
+    drop(lease);
+    retry::until_ok(|| async {
+        let released = client.metrics().drop_releases == 1;
+        released.then_some(()).context("not released")
+    })
+    .await;
+    let metrics = client.metrics();
+    assert_eq!(metrics.drop_release_retries, 2);
+    assert_eq!(metrics.leaked_leases, 0);
+    assert_eq!(store.calls(Op::Delete), 3);
+    let lease2 = client2
+        .try_acquire("drop_release_faults")
+        .await
+        .unwrap()
+        .expect("should acquire after release");
+    lease2.release().await.unwrap();
+
+    // faults outlasting the budget leak the lease until expiry
+    let lease = client.acquire("drop_release_faults2").await.unwrap();
+    let calls = store.calls(Op::Delete);
+    store.fail(Op::Delete, Fault::ServerError, usize::MAX);
+    drop(lease);
+    retry::until_ok(|| async {
+        let leaked = client.metrics().leaked_leases == 1;
+        leaked.then_some(()).context("not leaked")
+    })
+    .await;
+    let metrics = client.metrics();
+    assert_eq!(metrics.drop_releases, 1);
+    assert_eq!(metrics.drop_release_retries, 4);
+    assert_eq!(store.calls(Op::Delete), calls + 3);
+    assert!(
+        client2
+            .try_acquire("drop_release_faults2")
+            .await
+            .unwrap()
+            .is_none(),
+        "leaked lease should block until expiry"
+    );
+}
+
+#[tokio::test]
+async fn drop_expired_lease() {
+    let store = FaultyStore::default();
+    let client = Client::builder()
+        .lease_ttl_seconds(2)
+        .extend_every(Duration::from_millis(100))
+        .build_with_store(store.clone())
+        .unwrap();
+
+    let lease = client.acquire("drop_expired_lease").await.unwrap();
+    store.fail(Op::Extend, Fault::Throttle, usize::MAX);
+    let reason = tokio::time::timeout(TEST_WAIT, lease.lost())
+        .await
+        .expect("lease should expire");
+    assert_eq!(reason, LostReason::Expired);
+
+    // lost leases that expired are not deleted, another holder may have acquired them
+    drop(lease);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(store.calls(Op::Delete), 0);
+    let metrics = client.metrics();
+    assert_eq!(metrics.drop_releases, 0);
+    assert_eq!(metrics.leaked_leases, 0);
+}
+
+#[tokio::test]
+async fn describe() {
+    let client = Client::builder()
+        .owner("worker-1")
+        .lease_attribute("job_id", "job-123")
+        .build_with_store(MemoryStore::default())
+        .unwrap();
+
+    assert!(client.describe("describe").await.unwrap().is_none());
+
+    let lease = client.acquire("describe").await.unwrap();
+    let info = client
+        .describe("describe")
+        .await
+        .unwrap()
+        .expect("missing lease");
+    assert_eq!(info.key, "describe");
+    assert_eq!(info.lease_v, lease.lease_v().await);
+    assert_eq!(info.fencing_token, lease.fencing_token());
+    assert_eq!(info.owner.as_deref(), Some("worker-1"));
+    assert_eq!(info.attributes.get("job_id").unwrap(), "job-123");
+    assert!(!info.is_expired());
+
+    lease.release().await.unwrap();
+    assert!(client.describe("describe").await.unwrap().is_none());
+}
+
+#[test]
+fn reserved_lease_attribute() {
+    for name in [
+        "key",
+        "lease_expiry",
+        "lease_version",
+        "lease_owner",
+        "lease_readers",
+    ] {
+        let err = Client::builder()
+            .lease_attribute(name, "x")
+            .build_with_store(MemoryStore::default())
+            .unwrap_err();
+        assert!(err.is_invalid_options(), "{err:?}");
+    }
+
+    // configured attribute names are reserved
+    let err = Client::builder()
+        .lease_expiry_attribute_name("expires_at")
+        .lease_attribute("expires_at", "x")
+        .build_with_store(MemoryStore::default())
+        .unwrap_err();
+    assert!(err.is_invalid_options(), "{err:?}");
+    Client::builder()
+        .lease_expiry_attribute_name("expires_at")
+        .lease_attribute("lease_expiry", "x")
+        .build_with_store(MemoryStore::default())
+        .expect("default name is no longer reserved");
+}
+
+#[tokio::test]
+async fn replace_expired_attributes() {
+    let store = MemoryStore::default();
+    let expired = Client::builder()
+        .lease_ttl_seconds(2)
+        .owner("worker-1")
+        .lease_attribute("job_id", "job-123")
+        .build_with_store(store.clone())
+        .unwrap();
+    let client = Client::builder()
+        .owner("worker-2")
+        .build_with_store(store)
+        .unwrap();
+
+    // stop extension so the lease expires
+    let lease = expired.acquire("replace_expired_attributes").await.unwrap();
+    let _ = lease.into_handoff_token().await;
+
+    let lease = tokio::time::timeout(TEST_WAIT, client.acquire("replace_expired_attributes"))
+        .await
+        .expect("should acquire after expiry")
+        .unwrap();
+    let info = client
+        .describe("replace_expired_attributes")
+        .await
+        .unwrap()
+        .expect("missing lease");
+    assert_eq!(info.owner.as_deref(), Some("worker-2"));
+    assert!(info.attributes.is_empty(), "{:?}", info.attributes);
+    lease.release().await.unwrap();
+}
+
+#[tokio::test]
+async fn list_leases() {
+    let client = Client::builder()
+        .build_with_store(MemoryStore::default())
+        .unwrap();
+
+    let _a = client.acquire("list/a").await.unwrap();
+    let _b = client.acquire("list/b").await.unwrap();
+    let _other = client.acquire("other").await.unwrap();
+
+    let keys = |options: ListOptions| {
+        let client = client.clone();
+        async move {
+            let leases: Vec<_> = client.list_leases(&options).try_collect().await.unwrap();
+            leases.into_iter().map(|l| l.key).collect::<Vec<_>>()
+        }
+    };
+    assert_eq!(
+        keys(ListOptions::default()).await,
+        ["list/a", "list/b", "other"]
+    );
+    assert_eq!(
+        keys(ListOptions::default().prefix("list/").hide_expired(true)).await,
+        ["list/a", "list/b"]
+    );
+}
+
+#[tokio::test]
+async fn force_release() {
+    let store = MemoryStore::default();
+    let client = Client::builder()
+        .lease_ttl_seconds(2)
+        .extend_every(Duration::from_millis(100))
+        .build_with_store(store.clone())
+        .unwrap();
+    let admin = Client::builder()
+        .owner("admin")
+        .build_with_store(store)
+        .unwrap();
+
+    let err = admin
+        .force_release("force_release", None)
+        .await
+        .expect_err("no lease to release");
+    assert!(err.is_lease_lost(), "{err:?}");
+
+    let lease = client.acquire("force_release").await.unwrap();
+    let err = admin
+        .force_release("force_release", Some(uuid::Uuid::new_v4()))
+        .await
+        .expect_err("should not release a different version");
+    assert!(err.is_lease_lost(), "{err:?}");
+
+    admin.force_release("force_release", None).await.unwrap();
+    let info = admin.describe("force_release").await.unwrap().unwrap();
+    assert_eq!(info.revoked_by.as_deref(), Some("admin"));
+    assert!(info.is_expired());
+
+    // the evicted holder should notice on its next extension
+    let reason = tokio::time::timeout(TEST_WAIT, lease.lost()).await.unwrap();
+    assert_eq!(reason, LostReason::Revoked);
+
+    // the key can be immediately acquired
+    let _lease = admin
+        .try_acquire("force_release")
+        .await
+        .unwrap()
+        .expect("should acquire after force release");
+    let info = admin.describe("force_release").await.unwrap().unwrap();
+    assert_eq!(info.revoked_by, None);
+}
+
+#[tokio::test]
+async fn permits() {
+    let store = MemoryStore::default();
+    let client = Client::builder().build_with_store(store.clone()).unwrap();
+    let client2 = Client::builder()
+        .acquire_cooldown(Duration::from_millis(10))
+        .build_with_store(store)
+        .unwrap();
+
+    let permit1 = client.acquire_permit("permits", 2).await.unwrap();
+    let permit2 = client2.acquire_permit("permits", 2).await.unwrap();
+    assert_ne!(permit1.slot(), permit2.slot());
+
+    // all permits are held
+    assert!(
+        client
+            .try_acquire_permit("permits", 2)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    drop(permit1);
+    let permit3 = client2
+        .acquire_permit_timeout("permits", 2, TEST_WAIT)
+        .await
+        .expect("failed to acquire");
+    assert_ne!(permit3.slot(), permit2.slot());
+}
+
+#[tokio::test]
+async fn shared_exclusive() {
+    let store = MemoryStore::default();
+    let client = Client::builder()
+        .lease_ttl_seconds(2)
+        .extend_every(Duration::from_millis(100))
+        .build_with_store(store.clone())
+        .unwrap();
+    let client2 = Client::builder().build_with_store(store).unwrap();
+
+    let reader1 = client.acquire_shared("shared_exclusive").await.unwrap();
+    let reader2 = client2
+        .try_acquire_shared("shared_exclusive")
+        .await
+        .unwrap()
+        .expect("shared leases should coexist");
+    assert!(reader1.is_shared());
+    assert_eq!(reader1.fencing_token(), reader2.fencing_token());
+
+    // exclusive is blocked by shared holders
+    assert!(
+        client2
+            .try_acquire("shared_exclusive")
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    // shared leases are extended independently
+    let lease_v = reader1.lease_v().await;
+    retry::until_ok(|| async {
+        match reader1.lease_v().await {
+            v if v == lease_v => anyhow::bail!("not extended"),
+            _ => Ok(()),
+        }
+    })
+    .await;
+
+    reader1.release().await.unwrap();
+    assert!(
+        client2
+            .try_acquire("shared_exclusive")
+            .await
+            .unwrap()
+            .is_none()
+    );
+    reader2.release().await.unwrap();
+
+    let writer = client2
+        .try_acquire("shared_exclusive")
+        .await
+        .unwrap()
+        .expect("should acquire exclusive after shared released");
+    assert!(!writer.is_shared());
+
+    // shared is blocked by an exclusive holder
+    assert!(
+        client
+            .try_acquire_shared("shared_exclusive")
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn shared_prune_expired() {
+    let store = MemoryStore::default();
+    let expired = Client::builder()
+        .lease_ttl_seconds(2)
+        .build_with_store(store.clone())
+        .unwrap();
+    let client = Client::builder().build_with_store(store).unwrap();
+
+    // stop extension so the shared holder expires
+    let reader = expired
+        .acquire_shared("shared_prune_expired")
+        .await
+        .unwrap();
+    let _ = reader.into_handoff_token().await;
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    // joining removes the expired holder, so releasing the last active holder deletes the item
+    let reader = client.acquire_shared("shared_prune_expired").await.unwrap();
+    reader.release().await.unwrap();
+    assert!(
+        client
+            .describe("shared_prune_expired")
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn acquire_all() {
+    let store = MemoryStore::default();
+    let client = Client::builder()
+        .lease_ttl_seconds(2)
+        .extend_every(Duration::from_millis(100))
+        .build_with_store(store.clone())
+        .unwrap();
+    let client2 = Client::builder().build_with_store(store).unwrap();
+
+    let held = client2.try_acquire("acquire_all:b").await.unwrap().unwrap();
+
+    // none are acquired if any is held
+    assert!(
+        client
+            .try_acquire_all(["acquire_all:a", "acquire_all:b"])
+            .await
+            .unwrap()
+            .is_none()
+    );
+    assert!(client.describe("acquire_all:a").await.unwrap().is_none());
+    drop(held);
+
+    let leases = client
+        .acquire_all(
+            ["acquire_all:b", "acquire_all:a", "acquire_all:b"],
+            TEST_WAIT,
+        )
+        .await
+        .unwrap();
+    let keys: Vec<_> = leases.leases().iter().map(|l| l.key()).collect();
+    assert_eq!(keys, ["acquire_all:a", "acquire_all:b"]);
+
+    // local & remote acquisition is blocked
+    assert!(client.try_acquire("acquire_all:a").await.unwrap().is_none());
+    assert!(
+        client2
+            .try_acquire("acquire_all:b")
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    // leases are extended together
+    let lease_v = leases.lease("acquire_all:b").unwrap().lease_v().await;
+    retry::until_ok(|| async {
+        match leases.lease("acquire_all:b").unwrap().lease_v().await {
+            v if v == lease_v => anyhow::bail!("not extended"),
+            _ => Ok(()),
+        }
+    })
+    .await;
+
+    leases.release().await.unwrap();
+    assert!(client.describe("acquire_all:a").await.unwrap().is_none());
+    assert!(client.describe("acquire_all:b").await.unwrap().is_none());
+    client
+        .try_acquire_all(["acquire_all:a", "acquire_all:b"])
+        .await
+        .unwrap()
+        .expect("should acquire after release");
+}
+
+#[tokio::test]
+async fn drop_lost_multi_lease() {
+    let store = MemoryStore::default();
+    let client = Client::builder()
+        .lease_ttl_seconds(2)
+        .extend_every(Duration::from_millis(100))
+        .build_with_store(store.clone())
+        .unwrap();
+    let client2 = Client::builder().build_with_store(store).unwrap();
+
+    let leases = client
+        .acquire_all(["drop_lost_multi:a", "drop_lost_multi:b"], TEST_WAIT)
+        .await
+        .unwrap();
+
+    // revoking one key loses the whole group
+    client2
+        .force_release("drop_lost_multi:a", None)
+        .await
+        .unwrap();
+    let reason = tokio::time::timeout(TEST_WAIT, leases.lost())
+        .await
+        .unwrap();
+    assert_eq!(reason, LostReason::Revoked);
+
+    // other keys are still released on drop
+    drop(leases);
+    retry::until_ok(|| async {
+        match client.describe("drop_lost_multi:b").await? {
+            Some(_) => anyhow::bail!("not released"),
+            None => Ok(()),
+        }
+    })
+    .await;
+    assert_eq!(client.metrics().leaked_leases, 0);
+}
+
+#[tokio::test]
+async fn leader_election() {
+    let store = MemoryStore::default();
+    let client = Client::builder()
+        .acquire_cooldown(Duration::from_millis(10))
+        .build_with_store(store.clone())
+        .unwrap();
+    let client2 = Client::builder()
+        .acquire_cooldown(Duration::from_millis(10))
+        .build_with_store(store)
+        .unwrap();
+
+    let election_a = client.leader_election("leader_election", "a");
+    let mut leadership_a = election_a.watch();
+    tokio::time::timeout(TEST_WAIT, leadership_a.wait_for(Leadership::is_leader))
+        .await
+        .unwrap()
+        .unwrap();
+
+    // followers observe the leader id
+    let election_b = client2.leader_election("leader_election", "b");
+    let mut leadership_b = election_b.watch();
+    tokio::time::timeout(
+        TEST_WAIT,
+        leadership_b
+            .wait_for(|l| matches!(l, Leadership::Follower { leader: Some(id) } if id == "a")),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert!(election_a.is_leader());
+    assert!(!election_b.is_leader());
+
+    // stepping down hands leadership to another candidate
+    election_a.step_down().await.unwrap();
+    tokio::time::timeout(TEST_WAIT, leadership_b.wait_for(Leadership::is_leader))
+        .await
+        .unwrap()
+        .unwrap();
+    let info = client.describe("leader_election").await.unwrap().unwrap();
+    assert_eq!(info.owner.as_deref(), Some("b"));
+}
+
+#[tokio::test]
+async fn fair_acquire() {
+    let store = MemoryStore::default();
+    let fair_client = || {
+        Client::builder()
+            .acquire_cooldown(Duration::from_millis(10))
+            .fair_acquire(true)
+            .build_with_store(store.clone())
+            .unwrap()
+    };
+    let (client_a, client_b, client_c) = (fair_client(), fair_client(), fair_client());
+
+    let lease_a = client_a.acquire("fair_acquire").await.unwrap();
+
+    let waiter_b = tokio::spawn(async move { client_b.acquire("fair_acquire").await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let waiter_c = tokio::spawn(async move { client_c.acquire("fair_acquire").await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // the oldest waiter is granted the lease first
+    drop(lease_a);
+    let lease_b = tokio::time::timeout(TEST_WAIT, waiter_b)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!waiter_c.is_finished());
+
+    drop(lease_b);
+    tokio::time::timeout(TEST_WAIT, waiter_c)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn fair_acquire_long_cooldown() {
+    let store = MemoryStore::default();
+    let holder = Client::builder().build_with_store(store.clone()).unwrap();
+    // cooldown longer than the ticket ttl
+    let client_b = Client::builder()
+        .lease_ttl_seconds(2)
+        .acquire_cooldown(Duration::from_secs(10))
+        .fair_acquire(true)
+        .build_with_store(store.clone())
+        .unwrap();
+    let client_c = Client::builder()
+        .lease_ttl_seconds(2)
+        .acquire_cooldown(Duration::from_millis(10))
+        .fair_acquire(true)
+        .build_with_store(store)
+        .unwrap();
+
+    let lease_a = holder.acquire("fair_acquire_long_cooldown").await.unwrap();
+
+    let waiter_b =
+        tokio::spawn(async move { client_b.acquire("fair_acquire_long_cooldown").await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let waiter_c =
+        tokio::spawn(async move { client_c.acquire("fair_acquire_long_cooldown").await });
+
+    // the oldest waiter keeps its place in the queue past the ticket ttl
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    drop(lease_a);
+    let _lease_b = tokio::time::timeout(TEST_WAIT, waiter_b)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(!waiter_c.is_finished());
+}
+
+#[tokio::test]
+async fn expiry_aware_acquire() {
+    let store = MemoryStore::default();
+    let holder = Client::builder()
+        .lease_ttl_seconds(2)
+        .build_with_store(store.clone())
+        .unwrap();
+    // long cooldown, so only acquires promptly if waiting for the holder's expiry
+    let client = Client::builder()
+        .acquire_cooldown(Duration::from_secs(60))
+        .acquire_strategy(AcquireStrategy::ExpiryAware {
+            max_cooldown: Duration::from_secs(60),
+        })
+        .build_with_store(store)
+        .unwrap();
+
+    // simulate a crashed holder that no longer extends its lease
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        std::mem::forget(rt.block_on(holder.acquire("expiry_aware_acquire")).unwrap());
+    })
+    .join()
+    .unwrap();
+
+    // bails early when the holder outlives the max wait
+    let start = Instant::now();
+    let err = client
+        .acquire_timeout("expiry_aware_acquire", Duration::from_millis(500))
+        .await
+        .expect_err("holder lease should outlive max wait");
+    assert!(err.is_timeout(), "{err:?}");
+    assert!(start.elapsed() < Duration::from_millis(250));
+
+    // retries as soon as the holder's lease expires
+    tokio::time::timeout(
+        Duration::from_secs(5),
+        client.acquire("expiry_aware_acquire"),
+    )
+    .await
+    .expect("should acquire after holder expiry")
+    .unwrap();
+}
+
+#[tokio::test]
+async fn clock_skew_unmeasured() {
+    let client = Client::builder()
+        .correct_clock_skew(true)
+        .build_with_store(MemoryStore::default())
+        .unwrap();
+
+    // custom stores provide no server time
+    let lease = client.acquire("clock_skew_unmeasured").await.unwrap();
+    assert_eq!(client.clock_skew(), None);
+    lease.release().await.unwrap();
+}
+
+#[tokio::test]
+async fn handoff() {
+    let store = MemoryStore::default();
+    let old = Client::builder()
+        .owner("old-pod")
+        .build_with_store(store.clone())
+        .unwrap();
+    let new = Client::builder()
+        .owner("new-pod")
+        .build_with_store(store.clone())
+        .unwrap();
+
+    let lease = old.acquire("handoff").await.unwrap();
+    let fencing_token = lease.fencing_token();
+    let token = lease.into_handoff_token().await.to_string();
+
+    // still held while handing off
+    assert!(old.try_acquire("handoff").await.unwrap().is_none());
+
+    let token: HandoffToken = token.parse().unwrap();
+    assert_eq!(token.key(), "handoff");
+    let lease = new.adopt(token.clone()).await.unwrap();
+    assert_eq!(lease.fencing_token(), fencing_token);
+    let info = new.describe("handoff").await.unwrap().unwrap();
+    assert_eq!(info.owner.as_deref(), Some("new-pod"));
+
+    // the token is stale once adopted
+    let err = old.adopt(token).await.unwrap_err();
+    assert!(err.is_lease_lost(), "{err:?}");
+    let err = "nonsense".parse::<HandoffToken>().unwrap_err();
+    assert!(err.is_invalid_token(), "{err:?}");
+
+    lease.release().await.unwrap();
+    assert!(old.try_acquire("handoff").await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn reclaim() {
+    let store = MemoryStore::default();
+    let crashed = Client::builder()
+        .owner("worker:1")
+        .extend_every(Duration::from_millis(100))
+        .build_with_store(store.clone())
+        .unwrap();
+
+    // simulate a holder that crashes after persisting its latest identity
+    let identity = std::thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let lease = crashed.acquire("reclaim").await.unwrap();
+            let acquired = lease.identity();
+            let mut identity = lease.watch_identity();
+            identity.changed().await.unwrap();
+            let extended = identity.borrow().clone();
+            assert_ne!(extended.lease_v, acquired.lease_v);
+            std::mem::forget(lease);
+            extended.to_string()
+        })
+    })
+    .join()
+    .unwrap();
+
+    let identity: LeaseIdentity = identity.parse().unwrap();
+    assert_eq!(identity.owner, "worker:1");
+    assert_eq!(identity.key, "reclaim");
+
+    // a restarted process reclaims without waiting for expiry
+    let client = Client::builder().build_with_store(store).unwrap();
+    let lease = client.reclaim(identity.clone()).await.unwrap();
+    assert_eq!(lease.fencing_token(), identity.fencing_token);
+    assert_eq!(lease.identity().owner, "worker:1");
+    assert!(client.try_acquire("reclaim").await.unwrap().is_none());
+
+    // the persisted identity is stale once reclaimed
+    let err = client.reclaim(identity).await.unwrap_err();
+    assert!(err.is_lease_lost(), "{err:?}");
+
+    lease.release().await.unwrap();
+}
+
+#[tokio::test]
+async fn acquire_with() {
+    let client = Client::builder()
+        .lease_ttl_seconds(60)
+        .build_with_store(MemoryStore::default())
+        .unwrap();
+
+    let short = AcquireOptions::default()
+        .lease_ttl_seconds(5)
+        .acquire_cooldown(Duration::from_millis(10))
+        .timeout(Duration::from_millis(100));
+    let lease = client.acquire_with("acquire_with", &short).await.unwrap();
+    let info = client.describe("acquire_with").await.unwrap().unwrap();
+    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    assert!(info.expiry <= now + 5, "{info:?}");
+
+    // times out while held
+    let err = client
+        .acquire_with("acquire_with", &short)
+        .await
+        .unwrap_err();
+    assert!(err.is_timeout(), "{err:?}");
+    assert!(
+        client
+            .try_acquire_with("acquire_with", &short)
+            .await
+            .unwrap()
+            .is_none()
+    );
+    lease.release().await.unwrap();
+
+    // invalid options are errors rather than panics
+    let invalid = [
+        AcquireOptions::default().lease_ttl_seconds(1),
+        AcquireOptions::default().extend_every(Duration::ZERO),
+        AcquireOptions::default().extend_every(Duration::from_secs(60)),
+        AcquireOptions::default()
+            .lease_ttl_seconds(5)
+            .extend_every(Duration::from_secs(5)),
+    ];
+    for options in invalid {
+        let err = client
+            .acquire_with("acquire_with", &options)
+            .await
+            .unwrap_err();
+        assert!(err.is_invalid_options(), "{options:?} {err:?}");
+    }
+}
+
+#[tokio::test]
+async fn manual_extend() {
+    let client = Client::builder()
+        .lease_ttl_seconds(2)
+        .auto_extend(false)
+        .build_with_store(MemoryStore::default())
+        .unwrap();
+
+    let lease = client.acquire("manual_extend").await.unwrap();
+    let lease_v = lease.lease_v().await;
+    let expiry = lease.extend(Some(Duration::from_secs(3))).await.unwrap();
+    let now = time::OffsetDateTime::now_utc();
+    assert!(expiry > now + time::Duration::seconds(2), "{expiry}");
+    assert_ne!(lease.lease_v().await, lease_v);
+    lease.extend(None).await.unwrap();
+
+    let err = lease
+        .extend(Some(Duration::from_secs(1)))
+        .await
+        .unwrap_err();
+    assert!(err.is_invalid_options(), "{err:?}");
+
+    // without progress the lease expires, as it is not extended in the background
+    let reason = tokio::time::timeout(Duration::from_secs(5), lease.lost())
+        .await
+        .expect("lease should expire");
+    assert_eq!(reason, LostReason::Expired);
+    let err = lease.extend(None).await.unwrap_err();
+    assert!(err.is_lease_lost(), "{err:?}");
+    drop(lease);
+    tokio::time::timeout(Duration::from_secs(5), client.acquire("manual_extend"))
+        .await
+        .expect("should acquire after expiry")
+        .unwrap();
+
+    // per acquire override
+    let auto = AcquireOptions::default().auto_extend(true);
+    let lease = client.acquire_with("manual_extend2", &auto).await.unwrap();
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    assert_eq!(lease.status(), LeaseStatus::Held);
+}
@@ -2,10 +2,15 @@ mod util;
 
 use anyhow::Context;
 use aws_sdk_dynamodb::types::{
-    AttributeDefinition, BillingMode, KeySchemaElement, KeyType, ScalarAttributeType,
+    AttributeDefinition, AttributeValue, BillingMode, KeySchemaElement, KeyType,
+    ScalarAttributeType,
 };
+use dynamodb_lease::{
+    AcquireOptions, AcquireStrategy, HandoffToken, KeySplit, Leadership, LeaseIdentity,
+    LeaseStatus, ListOptions, LostReason, Metrics, TableOptions,
+};
+use futures_util::TryStreamExt;
 use std::time::Duration;
-use time;
 use util::*;
 use uuid::Uuid;
 
@@ -46,8 +51,8 @@ async fn try_acquire() {
     retry::until_ok(|| async {
         client2
             .try_acquire(&lease_key)
-            .await
-            .and_then(|maybe_lease| maybe_lease.context("did not acquire"))
+            .await?
+            .context("did not acquire")
     })
     .await;
     let _ = instance.stop().await;
@@ -84,8 +89,8 @@ async fn local_try_acquire() {
     retry::until_ok(|| async {
         client
             .try_acquire(&lease_key)
-            .await
-            .and_then(|maybe_lease| maybe_lease.context("did not acquire"))
+            .await?
+            .context("did not acquire")
     })
     .await;
     let _ = instance.stop().await;
@@ -292,7 +297,10 @@ async fn acquire_timeout() {
     let lease2 = client2
         .acquire_timeout(&lease_key, Duration::from_millis(100))
         .await;
-    assert!(lease2.is_err(), "should not acquire while lease1 is alive");
+    assert!(
+        lease2.is_err_and(|err| err.is_timeout()),
+        "should not acquire while lease1 is alive"
+    );
 
     // dropping should asynchronously end the lease
     drop(lease1);
@@ -314,6 +322,7 @@ async fn init_should_check_table_exists() {
         .build_and_check_db(db_client)
         .await
         .expect_err("should check table exists");
+    assert!(err.is_table_not_found(), "{err:?}");
     assert!(
         err.to_string().to_ascii_lowercase().contains("missing"),
         "{}",
@@ -392,6 +401,7 @@ async fn init_should_check_hash_key_type() {
         .build_and_check_db(db_client)
         .await
         .expect_err("should check hash key type");
+    assert!(err.is_invalid_schema(), "{err:?}");
     assert!(
         err.to_string().to_ascii_lowercase().contains("type"),
         "{}",
@@ -495,3 +505,1303 @@ async fn try_acquire_replaces_expired() {
 
     let _ = instance.stop().await;
 }
+
+#[tokio::test]
+async fn lease_lost_when_replaced() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .lease_ttl_seconds(2)
+        .build_and_check_db(db_client.clone())
+        .await
+        .unwrap();
+
+    let lease_key = format!("lease_lost_when_replaced:{}", Uuid::new_v4());
+
+    let lease = client.acquire(&lease_key).await.unwrap();
+    assert_eq!(lease.status(), LeaseStatus::Held);
+
+    // Replace the lease item with a different version, simulating another holder
+    db_client
+        .put_item()
+        .table_name(lease_table)
+        .item(
+            "key",
+            aws_sdk_dynamodb::types::AttributeValue::S(lease_key.clone()),
+        )
+        .item(
+            "lease_expiry",
+            aws_sdk_dynamodb::types::AttributeValue::N(
+                (time::OffsetDateTime::now_utc().unix_timestamp() + 60).to_string(),
+            ),
+        )
+        .item(
+            "lease_version",
+            aws_sdk_dynamodb::types::AttributeValue::S(Uuid::new_v4().to_string()),
+        )
+        .send()
+        .await
+        .expect("Failed to replace lease item");
+
+    // the next background extension should notice
+    let reason = tokio::time::timeout(TEST_WAIT, lease.lost())
+        .await
+        .expect("lease loss not observed");
+    assert_eq!(reason, LostReason::Replaced);
+    assert_eq!(lease.status(), LeaseStatus::Lost { reason });
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn drop_release_metrics() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .build_and_check_db(db_client)
+        .await
+        .unwrap();
+
+    let lease_key = format!("drop_release_metrics:{}", Uuid::new_v4());
+
+    let lease = client.acquire(&lease_key).await.unwrap();
+    assert_eq!(client.metrics(), Metrics::default());
+
+    drop(lease);
+
+    // dropped lease should be released & counted
+    retry::until_ok(|| async {
+        match client.metrics().drop_releases {
+            1 => Ok(()),
+            n => Err(anyhow::anyhow!("drop_releases = {n}")),
+        }
+    })
+    .await;
+    assert_eq!(client.metrics().leaked_leases, 0);
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn fencing_token_increases() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .build_and_check_db(db_client.clone())
+        .await
+        .unwrap();
+    // use 2 clients to avoid local locking / simulate distributed usage
+    let client2 = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .build_and_check_db(db_client.clone())
+        .await
+        .unwrap();
+
+    let lease_key = format!("fencing_token_increases:{}", Uuid::new_v4());
+
+    let lease = client.acquire(&lease_key).await.unwrap();
+    let token1 = lease.fencing_token();
+    let lease_v = lease.lease_v().await;
+    lease.release().await.unwrap();
+
+    // deleted item, token should still increase
+    let lease2 = client.acquire(&lease_key).await.unwrap();
+    let token2 = lease2.fencing_token();
+    assert_eq!(token2, token1 + 1);
+
+    // expire the lease so it can be replaced
+    db_client
+        .update_item()
+        .table_name(lease_table)
+        .key(
+            "key",
+            aws_sdk_dynamodb::types::AttributeValue::S(lease_key.clone()),
+        )
+        .update_expression("SET lease_expiry = :expiry")
+        .expression_attribute_values(
+            ":expiry",
+            aws_sdk_dynamodb::types::AttributeValue::N(
+                (time::OffsetDateTime::now_utc().unix_timestamp() - 1000).to_string(),
+            ),
+        )
+        .send()
+        .await
+        .expect("Failed to expire lease item");
+
+    // replacing an expired item increments its token
+    let lease3 = client2
+        .try_acquire(&lease_key)
+        .await
+        .unwrap()
+        .expect("should replace expired lease");
+    assert_eq!(lease3.fencing_token(), token2 + 1);
+    assert_ne!(lease3.lease_v().await, lease_v);
+    assert_ne!(lease3.lease_v().await, lease2.lease_v().await);
+
+    // item deleted by time to live, token should still increase
+    db_client
+        .delete_item()
+        .table_name(lease_table)
+        .key("key", AttributeValue::S(lease_key.clone()))
+        .send()
+        .await
+        .expect("Failed to delete lease item");
+    let client3 = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .build_and_check_db(db_client.clone())
+        .await
+        .unwrap();
+    let lease4 = client3
+        .try_acquire(&lease_key)
+        .await
+        .unwrap()
+        .expect("should acquire deleted lease");
+    assert_eq!(lease4.fencing_token(), token2 + 2);
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn build_and_ensure_table() {
+    let lease_table = "test-locker-leases-created";
+    let (db_client, instance) = get_test_db().await;
+
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .build_and_ensure_table(db_client.clone())
+        .await
+        .expect("should create table");
+
+    // should be idempotent
+    client
+        .create_table(&TableOptions::default())
+        .await
+        .expect("should succeed for an existing table");
+
+    let lease_key = format!("build_and_ensure_table:{}", Uuid::new_v4());
+    client.acquire(&lease_key).await.unwrap();
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn custom_attribute_names() {
+    let lease_table = "test-locker-leases-custom-names";
+    let (db_client, instance) = get_test_db().await;
+
+    // use reserved words to check expression attribute names are used
+    let builder = || {
+        dynamodb_lease::Client::builder()
+            .table_name(lease_table)
+            .key_attribute_name("name")
+            .lease_expiry_attribute_name("ttl")
+            .lease_version_attribute_name("status")
+            .lease_generation_attribute_name("count")
+    };
+    let client = builder()
+        .build_and_ensure_table(db_client.clone())
+        .await
+        .unwrap();
+    let client2 = builder()
+        .build_and_check_db(db_client.clone())
+        .await
+        .unwrap();
+
+    let lease_key = format!("custom_attribute_names:{}", Uuid::new_v4());
+
+    let lease = client.acquire(&lease_key).await.unwrap();
+    assert!(client2.try_acquire(&lease_key).await.unwrap().is_none());
+
+    let item = db_client
+        .get_item()
+        .table_name(lease_table)
+        .key(
+            "name",
+            aws_sdk_dynamodb::types::AttributeValue::S(lease_key.clone()),
+        )
+        .send()
+        .await
+        .unwrap()
+        .item
+        .expect("missing lease item");
+    assert_eq!(
+        item.get("status").and_then(|v| v.as_s().ok()),
+        Some(&lease.lease_v().await.to_string())
+    );
+    assert!(item.contains_key("ttl"));
+    assert!(item.contains_key("count"));
+
+    lease.release().await.unwrap();
+    assert!(client2.try_acquire(&lease_key).await.unwrap().is_some());
+
+    // default names should fail the schema check
+    let err = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .build_and_check_db(db_client)
+        .await
+        .expect_err("should check key name");
+    assert!(err.is_invalid_schema(), "{err:?}");
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn composite_key_table() {
+    let lease_table = "test-locker-leases-composite";
+    let (db_client, instance) = get_test_db().await;
+
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .key_attribute_name("pk")
+        .composite_key("sk", KeySplit::FixedPartition("lease".into()))
+        .build_and_ensure_table(db_client.clone())
+        .await
+        .unwrap();
+    // leases split by "/" into the same table
+    let client2 = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .key_attribute_name("pk")
+        .composite_key(
+            "sk",
+            KeySplit::function(|key| {
+                let (pk, sk) = key.split_once('/').unwrap();
+                (pk.into(), sk.into())
+            }),
+        )
+        .build_and_check_db(db_client.clone())
+        .await
+        .unwrap();
+
+    let lease_key = format!("composite_key_table:{}", Uuid::new_v4());
+    let lease = client.acquire(&lease_key).await.unwrap();
+    assert!(
+        client2
+            .try_acquire(format!("lease/{lease_key}"))
+            .await
+            .unwrap()
+            .is_none(),
+        "should map to the same item"
+    );
+
+    let item = db_client
+        .get_item()
+        .table_name(lease_table)
+        .key("pk", AttributeValue::S("lease".into()))
+        .key("sk", AttributeValue::S(lease_key.clone()))
+        .send()
+        .await
+        .unwrap()
+        .item;
+    assert!(item.is_some(), "missing lease item");
+
+    lease.release().await.unwrap();
+    client2
+        .try_acquire(format!("lease/{lease_key}"))
+        .await
+        .unwrap()
+        .expect("should acquire after release");
+
+    // sort key values of internal items are reserved
+    let err = client2
+        .try_acquire(format!("lease/~lease/generation/{lease_key}"))
+        .await
+        .expect_err("should reject reserved sort key");
+    assert!(err.is_invalid_key(), "{err:?}");
+
+    // hash-only config should fail the schema check
+    let err = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .key_attribute_name("pk")
+        .build_and_check_db(db_client)
+        .await
+        .expect_err("should check key schema");
+    assert!(err.is_invalid_schema(), "{err:?}");
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn internal_item_keys() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .fair_acquire(true)
+        .build_and_check_db(db_client)
+        .await
+        .unwrap();
+
+    // keys resembling internal items are independent leases
+    let lease_key = format!("internal_item_keys:{}", Uuid::new_v4());
+    for key in [
+        lease_key.clone(),
+        format!("{lease_key}/generation"),
+        format!("{lease_key}/queue"),
+    ] {
+        let lease = client.acquire(&key).await.unwrap();
+        assert_eq!(lease.fencing_token(), 1, "{key}");
+        lease.release().await.unwrap();
+        let lease = client.acquire(&key).await.unwrap();
+        assert_eq!(lease.fencing_token(), 2, "{key}");
+        lease.release().await.unwrap();
+    }
+
+    let err = client
+        .try_acquire(format!("~lease/generation/{lease_key}"))
+        .await
+        .expect_err("should reject reserved key");
+    assert!(err.is_invalid_key(), "{err:?}");
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn lease_owner_attributes() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .lease_ttl_seconds(2)
+        .owner("worker-1")
+        .lease_attribute("job_id", "job-123")
+        .build_and_check_db(db_client.clone())
+        .await
+        .unwrap();
+    assert_eq!(client.owner(), "worker-1");
+
+    let lease_key = format!("lease_owner_attributes:{}", Uuid::new_v4());
+    let lease = client.acquire(&lease_key).await.unwrap();
+    let lease_v = lease.lease_v().await;
+
+    let get_item = || async {
+        db_client
+            .get_item()
+            .table_name(lease_table)
+            .key("key", AttributeValue::S(lease_key.clone()))
+            .consistent_read(true)
+            .send()
+            .await
+            .unwrap()
+            .item
+            .expect("missing lease item")
+    };
+    let item = get_item().await;
+    assert_eq!(
+        item.get("lease_owner").and_then(|v| v.as_s().ok()).unwrap(),
+        "worker-1"
+    );
+    assert_eq!(
+        item.get("job_id").and_then(|v| v.as_s().ok()).unwrap(),
+        "job-123"
+    );
+
+    // attributes should be preserved after extension
+    retry::until_ok(|| async {
+        match lease.lease_v().await {
+            v if v == lease_v => anyhow::bail!("not extended"),
+            _ => Ok(()),
+        }
+    })
+    .await;
+    let item = get_item().await;
+    assert!(item.contains_key("lease_owner"));
+    assert!(item.contains_key("job_id"));
+
+    // default owner includes the process id
+    let client2 = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .build(db_client)
+        .unwrap();
+    assert!(
+        client2
+            .owner()
+            .contains(&format!(":{}:", std::process::id())),
+        "{}",
+        client2.owner()
+    );
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn describe() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .owner("worker-1")
+        .lease_attribute("job_id", "job-123")
+        .build_and_check_db(db_client)
+        .await
+        .unwrap();
+
+    let lease_key = format!("describe:{}", Uuid::new_v4());
+    assert!(client.describe(&lease_key).await.unwrap().is_none());
+
+    let lease = client.acquire(&lease_key).await.unwrap();
+    let info = client
+        .describe(&lease_key)
+        .await
+        .unwrap()
+        .expect("missing lease");
+    assert_eq!(info.key, lease_key.as_str());
+    assert_eq!(info.lease_v, lease.lease_v().await);
+    assert_eq!(info.fencing_token, lease.fencing_token());
+    assert_eq!(info.owner.as_deref(), Some("worker-1"));
+    assert!(!info.is_expired());
+    assert_eq!(
+        info.attributes.into_iter().collect::<Vec<_>>(),
+        [("job_id".to_string(), "job-123".to_string())]
+    );
+
+    lease.release().await.unwrap();
+    assert!(client.describe(&lease_key).await.unwrap().is_none());
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn replace_expired_attributes() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let expired = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .lease_ttl_seconds(2)
+        .owner("worker-1")
+        .lease_attribute("job_id", "job-123")
+        .build_and_check_db(db_client.clone())
+        .await
+        .unwrap();
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .owner("worker-2")
+        .build_and_check_db(db_client)
+        .await
+        .unwrap();
+
+    // stop extension so the lease expires
+    let lease_key = format!("replace_expired_attributes:{}", Uuid::new_v4());
+    let lease = expired.acquire(&lease_key).await.unwrap();
+    let _ = lease.into_handoff_token().await;
+
+    let lease = tokio::time::timeout(TEST_WAIT, client.acquire(&lease_key))
+        .await
+        .expect("should acquire after expiry")
+        .unwrap();
+    let info = client
+        .describe(&lease_key)
+        .await
+        .unwrap()
+        .expect("missing lease");
+    assert_eq!(info.owner.as_deref(), Some("worker-2"));
+    assert!(info.attributes.is_empty(), "{:?}", info.attributes);
+    lease.release().await.unwrap();
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn list_leases() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .build_and_check_db(db_client.clone())
+        .await
+        .unwrap();
+
+    let _a = client.acquire("list/a").await.unwrap();
+    let _b = client.acquire("list/b").await.unwrap();
+    let _other = client.acquire("other").await.unwrap();
+
+    // an expired lease pending ttl deletion
+    db_client
+        .put_item()
+        .table_name(lease_table)
+        .item("key", AttributeValue::S("list/expired".into()))
+        .item("lease_expiry", AttributeValue::N("1".into()))
+        .item("lease_generation", AttributeValue::N("1".into()))
+        .item(
+            "lease_version",
+            AttributeValue::S(Uuid::new_v4().to_string()),
+        )
+        .send()
+        .await
+        .unwrap();
+
+    let keys = |options: ListOptions| {
+        let client = client.clone();
+        async move {
+            let leases: Vec<_> = client.list_leases(&options).try_collect().await.unwrap();
+            let mut keys: Vec<_> = leases.into_iter().map(|l| l.key).collect();
+            keys.sort();
+            keys
+        }
+    };
+    assert_eq!(
+        keys(ListOptions::default()).await,
+        ["list/a", "list/b", "list/expired", "other"]
+    );
+    assert_eq!(
+        keys(ListOptions::default().prefix("list/")).await,
+        ["list/a", "list/b", "list/expired"]
+    );
+    assert_eq!(
+        keys(ListOptions::default().prefix("list/").hide_expired(true)).await,
+        ["list/a", "list/b"]
+    );
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn list_leases_fixed_partition() {
+    let lease_table = "test-locker-leases-composite";
+    let (db_client, instance) = get_test_db().await;
+
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .composite_key("sk", KeySplit::FixedPartition("leases".into()))
+        .build_and_ensure_table(db_client)
+        .await
+        .unwrap();
+
+    let _a = client.acquire("list/a").await.unwrap();
+    let _other = client.acquire("other").await.unwrap();
+
+    let leases: Vec<_> = client
+        .list_leases(&ListOptions::default().prefix("list/"))
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(
+        leases.into_iter().map(|l| l.key).collect::<Vec<_>>(),
+        ["list/a"]
+    );
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn force_release() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .lease_ttl_seconds(2)
+        .extend_every(Duration::from_millis(500))
+        .build_and_check_db(db_client.clone())
+        .await
+        .unwrap();
+    let admin = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .owner("admin")
+        .build_and_check_db(db_client)
+        .await
+        .unwrap();
+
+    let lease_key = format!("force_release:{}", Uuid::new_v4());
+    let lease = client.acquire(&lease_key).await.unwrap();
+
+    let err = admin
+        .force_release(&lease_key, Some(Uuid::new_v4()))
+        .await
+        .expect_err("should not release a different version");
+    assert!(err.is_lease_lost(), "{err:?}");
+
+    let info = admin.describe(&lease_key).await.unwrap().unwrap();
+    admin
+        .force_release(&lease_key, Some(info.lease_v))
+        .await
+        .unwrap();
+    let info = admin.describe(&lease_key).await.unwrap().unwrap();
+    assert_eq!(info.revoked_by.as_deref(), Some("admin"));
+    assert!(info.is_expired());
+
+    // the evicted holder should notice on its next extension
+    let reason = tokio::time::timeout(TEST_WAIT, lease.lost()).await.unwrap();
+    assert_eq!(reason, LostReason::Revoked);
+
+    // the key can be immediately acquired
+    let _lease = admin
+        .try_acquire(&lease_key)
+        .await
+        .unwrap()
+        .expect("should acquire after force release");
+    let info = admin.describe(&lease_key).await.unwrap().unwrap();
+    assert_eq!(info.revoked_by, None);
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn permits() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .build_and_check_db(db_client.clone())
+        .await
+        .unwrap();
+    // use 2 clients to avoid local locking / simulate distributed usage
+    let client2 = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .acquire_cooldown(Duration::from_millis(100))
+        .build_and_check_db(db_client)
+        .await
+        .unwrap();
+
+    let name = format!("permits:{}", Uuid::new_v4());
+    let permit1 = client.acquire_permit(&name, 2).await.unwrap();
+    let permit2 = client2.acquire_permit(&name, 2).await.unwrap();
+    assert_ne!(permit1.slot(), permit2.slot());
+
+    // all permits are held
+    assert!(
+        client2
+            .try_acquire_permit(&name, 2)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    drop(permit1);
+    let permit3 = client2
+        .acquire_permit_timeout(&name, 2, TEST_WAIT)
+        .await
+        .expect("failed to acquire");
+    assert_ne!(permit3.slot(), permit2.slot());
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn shared_exclusive() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .lease_ttl_seconds(2)
+        .extend_every(Duration::from_millis(500))
+        .build_and_check_db(db_client.clone())
+        .await
+        .unwrap();
+    // use 2 clients to avoid local locking / simulate distributed usage
+    let client2 = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .build_and_check_db(db_client)
+        .await
+        .unwrap();
+
+    let lease_key = format!("shared_exclusive:{}", Uuid::new_v4());
+
+    let reader1 = client.acquire_shared(&lease_key).await.unwrap();
+    let reader2 = client2
+        .try_acquire_shared(&lease_key)
+        .await
+        .unwrap()
+        .expect("shared leases should coexist");
+    assert_eq!(reader1.fencing_token(), reader2.fencing_token());
+
+    // exclusive is blocked by shared holders
+    assert!(client2.try_acquire(&lease_key).await.unwrap().is_none());
+
+    // shared leases are extended independently, past the ttl
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    assert!(matches!(reader1.status(), LeaseStatus::Held));
+    assert!(client2.try_acquire(&lease_key).await.unwrap().is_none());
+
+    reader1.release().await.unwrap();
+    assert!(client2.try_acquire(&lease_key).await.unwrap().is_none());
+    reader2.release().await.unwrap();
+
+    let _writer = client2
+        .try_acquire(&lease_key)
+        .await
+        .unwrap()
+        .expect("should acquire exclusive after shared released");
+
+    // shared is blocked by an exclusive holder
+    assert!(
+        client
+            .try_acquire_shared(&lease_key)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn shared_prune_expired() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let expired = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .lease_ttl_seconds(2)
+        .build_and_check_db(db_client.clone())
+        .await
+        .unwrap();
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .build_and_check_db(db_client)
+        .await
+        .unwrap();
+
+    // stop extension so the shared holder expires
+    let lease_key = format!("shared_prune_expired:{}", Uuid::new_v4());
+    let reader = expired.acquire_shared(&lease_key).await.unwrap();
+    let _ = reader.into_handoff_token().await;
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    // joining removes the expired holder, so releasing the last active holder deletes the item
+    let reader = client.acquire_shared(&lease_key).await.unwrap();
+    reader.release().await.unwrap();
+    assert!(client.describe(&lease_key).await.unwrap().is_none());
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn acquire_all() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .lease_ttl_seconds(2)
+        .extend_every(Duration::from_millis(500))
+        .build_and_check_db(db_client.clone())
+        .await
+        .unwrap();
+    // use 2 clients to avoid local locking / simulate distributed usage
+    let client2 = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .build_and_check_db(db_client)
+        .await
+        .unwrap();
+
+    let key_a = format!("acquire_all:{}", Uuid::new_v4());
+    let key_b = format!("acquire_all:{}", Uuid::new_v4());
+
+    let held = client2.try_acquire(&key_b).await.unwrap().unwrap();
+
+    // none are acquired if any is held
+    assert!(
+        client
+            .try_acquire_all([&key_a, &key_b])
+            .await
+            .unwrap()
+            .is_none()
+    );
+    assert!(client.describe(&key_a).await.unwrap().is_none());
+    drop(held);
+
+    let leases = client
+        .acquire_all([&key_a, &key_b], TEST_WAIT)
+        .await
+        .unwrap();
+    assert!(client2.try_acquire(&key_a).await.unwrap().is_none());
+
+    // leases are extended together, past the ttl
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    assert!(matches!(leases.status(), LeaseStatus::Held));
+    assert!(client2.try_acquire(&key_b).await.unwrap().is_none());
+
+    leases.release().await.unwrap();
+    assert!(client.describe(&key_a).await.unwrap().is_none());
+    assert!(client.describe(&key_b).await.unwrap().is_none());
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn drop_lost_multi_lease() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .lease_ttl_seconds(2)
+        .extend_every(Duration::from_millis(500))
+        .build_and_check_db(db_client.clone())
+        .await
+        .unwrap();
+    let client2 = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .build_and_check_db(db_client)
+        .await
+        .unwrap();
+
+    let key_a = format!("drop_lost_multi:{}", Uuid::new_v4());
+    let key_b = format!("drop_lost_multi:{}", Uuid::new_v4());
+    let leases = client
+        .acquire_all([&key_a, &key_b], TEST_WAIT)
+        .await
+        .unwrap();
+
+    // revoking one key loses the whole group
+    client2.force_release(&key_a, None).await.unwrap();
+    let reason = tokio::time::timeout(TEST_WAIT, leases.lost())
+        .await
+        .unwrap();
+    assert_eq!(reason, LostReason::Revoked);
+
+    // other keys are still released on drop
+    drop(leases);
+    retry::until_ok(|| async {
+        match client.describe(&key_b).await? {
+            Some(_) => anyhow::bail!("not released"),
+            None => Ok(()),
+        }
+    })
+    .await;
+    assert_eq!(client.metrics().leaked_leases, 0);
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn leader_election() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .acquire_cooldown(Duration::from_millis(100))
+        .build_and_check_db(db_client.clone())
+        .await
+        .unwrap();
+    // use 2 clients to avoid local locking / simulate distributed usage
+    let client2 = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .acquire_cooldown(Duration::from_millis(100))
+        .build_and_check_db(db_client)
+        .await
+        .unwrap();
+
+    let lease_key = format!("leader_election:{}", Uuid::new_v4());
+
+    let election_a = client.leader_election(&lease_key, "a");
+    tokio::time::timeout(
+        TEST_WAIT,
+        election_a.watch().wait_for(Leadership::is_leader),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+
+    let election_b = client2.leader_election(&lease_key, "b");
+    let mut leadership_b = election_b.watch();
+    tokio::time::timeout(
+        TEST_WAIT,
+        leadership_b
+            .wait_for(|l| matches!(l, Leadership::Follower { leader: Some(id) } if id == "a")),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+
+    election_a.step_down().await.unwrap();
+    tokio::time::timeout(TEST_WAIT, leadership_b.wait_for(Leadership::is_leader))
+        .await
+        .unwrap()
+        .unwrap();
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn fair_acquire() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let fair_client = || {
+        dynamodb_lease::Client::builder()
+            .table_name(lease_table)
+            .acquire_cooldown(Duration::from_millis(100))
+            .fair_acquire(true)
+            .build(db_client.clone())
+            .unwrap()
+    };
+    let (client_a, client_b, client_c) = (fair_client(), fair_client(), fair_client());
+
+    let lease_key = format!("fair_acquire:{}", Uuid::new_v4());
+
+    let lease_a = client_a.acquire(&lease_key).await.unwrap();
+
+    let key = lease_key.clone();
+    let waiter_b = tokio::spawn(async move { client_b.acquire(key).await });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let key = lease_key.clone();
+    let waiter_c = tokio::spawn(async move { client_c.acquire(key).await });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // the oldest waiter is granted the lease first
+    drop(lease_a);
+    let lease_b = tokio::time::timeout(TEST_WAIT, waiter_b)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(!waiter_c.is_finished());
+
+    drop(lease_b);
+    tokio::time::timeout(TEST_WAIT, waiter_c)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+
+    // queue items are not listed as leases
+    let leases: Vec<_> = client_a
+        .list_leases(&ListOptions::default().prefix(&lease_key))
+        .try_collect()
+        .await
+        .unwrap();
+    assert!(leases.iter().all(|l| l.key == lease_key), "{leases:?}");
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn fair_acquire_long_cooldown() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let holder = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .build(db_client.clone())
+        .unwrap();
+    // cooldown longer than the ticket ttl
+    let client_b = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .lease_ttl_seconds(2)
+        .acquire_cooldown(Duration::from_secs(10))
+        .fair_acquire(true)
+        .build(db_client.clone())
+        .unwrap();
+    let client_c = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .lease_ttl_seconds(2)
+        .acquire_cooldown(Duration::from_millis(100))
+        .fair_acquire(true)
+        .build(db_client)
+        .unwrap();
+
+    let lease_key = format!("fair_acquire_long_cooldown:{}", Uuid::new_v4());
+
+    let lease_a = holder.acquire(&lease_key).await.unwrap();
+
+    let key = lease_key.clone();
+    let waiter_b = tokio::spawn(async move { client_b.acquire(key).await });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let key = lease_key.clone();
+    let waiter_c = tokio::spawn(async move { client_c.acquire(key).await });
+
+    // the oldest waiter keeps its place in the queue past the ticket ttl
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    drop(lease_a);
+    let _lease_b = tokio::time::timeout(TEST_WAIT, waiter_b)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(!waiter_c.is_finished());
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn expiry_aware_acquire() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    // long cooldown, so only acquires promptly if waiting for the holder's expiry
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .acquire_cooldown(Duration::from_secs(60))
+        .acquire_strategy(AcquireStrategy::ExpiryAware {
+            max_cooldown: Duration::from_secs(60),
+        })
+        .build_and_check_db(db_client.clone())
+        .await
+        .unwrap();
+
+    let lease_key = format!("expiry_aware_acquire:{}", Uuid::new_v4());
+
+    // a lease of a crashed holder that expires soon
+    let expiry = time::OffsetDateTime::now_utc().unix_timestamp() + 2;
+    db_client
+        .put_item()
+        .table_name(lease_table)
+        .item("key", AttributeValue::S(lease_key.clone()))
+        .item("lease_expiry", AttributeValue::N(expiry.to_string()))
+        .item(
+            "lease_version",
+            AttributeValue::S(Uuid::new_v4().to_string()),
+        )
+        .item("lease_generation", AttributeValue::N("1".into()))
+        .send()
+        .await
+        .unwrap();
+    db_client
+        .put_item()
+        .table_name(lease_table)
+        .item(
+            "key",
+            AttributeValue::S(format!("~lease/generation/{lease_key}")),
+        )
+        .item("lease_generation", AttributeValue::N("1".into()))
+        .send()
+        .await
+        .unwrap();
+
+    // bails early when the holder outlives the max wait
+    let err = client
+        .acquire_timeout(&lease_key, Duration::from_millis(500))
+        .await
+        .expect_err("holder lease should outlive max wait");
+    assert!(err.is_timeout(), "{err:?}");
+
+    // retries as soon as the holder's lease expires
+    let lease = tokio::time::timeout(Duration::from_secs(10), client.acquire(&lease_key))
+        .await
+        .expect("should acquire after holder expiry")
+        .unwrap();
+    assert_eq!(lease.fencing_token(), 2);
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn clock_skew() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .correct_clock_skew(true)
+        .build_and_check_db(db_client)
+        .await
+        .unwrap();
+
+    // measured from the check_db response
+    let skew = client.clock_skew().expect("skew should be measured");
+    assert!(skew.abs() < time::Duration::seconds(2), "{skew}");
+
+    // corrected clock still acquires & releases as usual
+    let lease_key = format!("clock_skew:{}", Uuid::new_v4());
+    let lease = client.acquire(&lease_key).await.unwrap();
+    assert!(client.try_acquire(&lease_key).await.unwrap().is_none());
+    lease.release().await.unwrap();
+    assert!(client.try_acquire(&lease_key).await.unwrap().is_some());
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn handoff() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let old = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .owner("old-pod")
+        .build_and_check_db(db_client.clone())
+        .await
+        .unwrap();
+    let new = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .owner("new-pod")
+        .build_and_check_db(db_client)
+        .await
+        .unwrap();
+
+    let lease_key = format!("handoff:{}", Uuid::new_v4());
+    let lease = old.acquire(&lease_key).await.unwrap();
+    let fencing_token = lease.fencing_token();
+    let token = lease.into_handoff_token().await.to_string();
+
+    // still held while handing off
+    assert!(old.try_acquire(&lease_key).await.unwrap().is_none());
+
+    let token: HandoffToken = token.parse().unwrap();
+    let lease = new.adopt(token.clone()).await.unwrap();
+    assert_eq!(lease.fencing_token(), fencing_token);
+    let info = new.describe(&lease_key).await.unwrap().unwrap();
+    assert_eq!(info.owner.as_deref(), Some("new-pod"));
+
+    // the token is stale once adopted
+    let err = old.adopt(token).await.unwrap_err();
+    assert!(err.is_lease_lost(), "{err:?}");
+
+    lease.release().await.unwrap();
+    assert!(old.try_acquire(&lease_key).await.unwrap().is_some());
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn reclaim() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let crashed = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .owner("worker:1")
+        .build_and_check_db(db_client.clone())
+        .await
+        .unwrap();
+
+    let lease_key = format!("reclaim:{}", Uuid::new_v4());
+    let lease = crashed.acquire(&lease_key).await.unwrap();
+    let identity = lease.identity().to_string();
+    // simulate a crash, the lease is no longer extended nor released
+    let _ = lease.into_handoff_token().await;
+    drop(crashed);
+
+    // a restarted process reclaims without waiting for expiry
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .build_and_check_db(db_client)
+        .await
+        .unwrap();
+    let identity: LeaseIdentity = identity.parse().unwrap();
+    let lease = client.reclaim(identity.clone()).await.unwrap();
+    assert_eq!(lease.fencing_token(), identity.fencing_token);
+    let info = client.describe(&lease_key).await.unwrap().unwrap();
+    assert_eq!(info.owner.as_deref(), Some("worker:1"));
+    assert!(client.try_acquire(&lease_key).await.unwrap().is_none());
+
+    // the persisted identity is stale once reclaimed
+    let err = client.reclaim(identity).await.unwrap_err();
+    assert!(err.is_lease_lost(), "{err:?}");
+
+    lease.release().await.unwrap();
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn acquire_with() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .lease_ttl_seconds(60)
+        .build_and_check_db(db_client)
+        .await
+        .unwrap();
+
+    let lease_key = format!("acquire_with:{}", Uuid::new_v4());
+    let short = AcquireOptions::default()
+        .lease_ttl_seconds(2)
+        .extend_every(Duration::from_millis(500))
+        .timeout(Duration::from_millis(100));
+    let lease = client.acquire_with(&lease_key, &short).await.unwrap();
+    let info = client.describe(&lease_key).await.unwrap().unwrap();
+    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    assert!(info.expiry <= now + 2, "{info:?}");
+
+    // extended with the short ttl
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    assert_eq!(lease.status(), LeaseStatus::Held);
+    let err = client.acquire_with(&lease_key, &short).await.unwrap_err();
+    assert!(err.is_timeout(), "{err:?}");
+
+    let err = client
+        .try_acquire_with(&lease_key, &AcquireOptions::default().lease_ttl_seconds(1))
+        .await
+        .unwrap_err();
+    assert!(err.is_invalid_options(), "{err:?}");
+
+    lease.release().await.unwrap();
+
+    let _ = instance.stop().await;
+}
+
+#[tokio::test]
+async fn manual_extend() {
+    let lease_table = "test-locker-leases";
+    let (db_client, instance) = get_test_db().await;
+    create_lease_table(lease_table, &db_client).await;
+
+    let client = dynamodb_lease::Client::builder()
+        .table_name(lease_table)
+        .lease_ttl_seconds(2)
+        .auto_extend(false)
+        .build_and_check_db(db_client)
+        .await
+        .unwrap();
+
+    let lease_key = format!("manual_extend:{}", Uuid::new_v4());
+    let lease = client.acquire(&lease_key).await.unwrap();
+    let expiry = lease.extend(Some(Duration::from_secs(3))).await.unwrap();
+    let info = client.describe(&lease_key).await.unwrap().unwrap();
+    assert_eq!(info.expires_at(), expiry);
+
+    // without progress the lease expires, as it is not extended in the background
+    let reason = tokio::time::timeout(Duration::from_secs(6), lease.lost())
+        .await
+        .expect("lease should expire");
+    assert_eq!(reason, LostReason::Expired);
+    let err = lease.extend(None).await.unwrap_err();
+    assert!(err.is_lease_lost(), "{err:?}");
+
+    drop(lease);
+
+    // revoked leases fail to extend
+    let lease = client.acquire(&lease_key).await.unwrap();
+    client.force_release(&lease_key, None).await.unwrap();
+    let err = lease.extend(None).await.unwrap_err();
+    assert!(err.is_lease_revoked(), "{err:?}");
+    assert_eq!(
+        lease.status(),
+        LeaseStatus::Lost {
+            reason: LostReason::Revoked
+        }
+    );
+
+    let _ = instance.stop().await;
+}
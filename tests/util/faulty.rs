@@ -0,0 +1,206 @@
+use async_trait::async_trait;
+use dynamodb_lease::{Error, LeaseInfo, LeaseStore, ListOptions, MemoryStore, NewLease, PutLease};
+use futures_core::stream::BoxStream;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+use uuid::Uuid;
+
+/// A failure injected by [`FaultyStore`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Throttled request, retryable.
+    Throttle,
+    /// 5xx response, retryable.
+    ServerError,
+    /// Failed condition, i.e. the lease has been deleted or replaced.
+    Conditional,
+}
+
+impl Fault {
+    fn error(self, operation: &'static str) -> Error {
+        match self {
+            Self::Throttle => Error::Throttled {
+                operation,
+                source: "injected throttling".into(),
+            },
+            Self::ServerError => Error::Db {
+                operation,
+                retryable: true,
+                source: "injected 500 internal server error".into(),
+            },
+            Self::Conditional => Error::LeaseLost,
+        }
+    }
+}
+
+/// Store operations that may fail with injected faults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Op {
+    /// Extending exclusive, multi & shared leases.
+    Extend,
+    /// Deleting exclusive, multi & shared leases.
+    Delete,
+}
+
+#[derive(Debug, Default)]
+struct Faults {
+    /// Fault & remaining failures of each op.
+    injected: HashMap<Op, (Fault, usize)>,
+    calls: HashMap<Op, usize>,
+}
+
+/// [`LeaseStore`] wrapping a [`MemoryStore`] that fails operations with injected faults.
+#[derive(Debug, Clone, Default)]
+pub struct FaultyStore {
+    inner: MemoryStore,
+    faults: Arc<Mutex<Faults>>,
+}
+
+impl FaultyStore {
+    /// Fails the next `times` calls of `op` with `fault`, replacing any previous fault.
+    pub fn fail(&self, op: Op, fault: Fault, times: usize) {
+        self.faults
+            .lock()
+            .unwrap()
+            .injected
+            .insert(op, (fault, times));
+    }
+
+    /// Returns the number of calls of `op` so far, including failed calls.
+    pub fn calls(&self, op: Op) -> usize {
+        self.faults
+            .lock()
+            .unwrap()
+            .calls
+            .get(&op)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Counts a call of `op`, returning the injected fault error if any.
+    fn call(&self, op: Op, operation: &'static str) -> Result<(), Error> {
+        let mut faults = self.faults.lock().unwrap();
+        *faults.calls.entry(op).or_default() += 1;
+        match faults.injected.get_mut(&op) {
+            Some((fault, times)) if *times > 0 => {
+                *times -= 1;
+                Err(fault.error(operation))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl LeaseStore for FaultyStore {
+    async fn put_lease(&self, lease: NewLease) -> Result<PutLease, Error> {
+        self.inner.put_lease(lease).await
+    }
+
+    async fn extend_lease(
+        &self,
+        key: &str,
+        lease_v: Uuid,
+        new_lease_v: Uuid,
+        expiry: i64,
+    ) -> Result<(), Error> {
+        self.call(Op::Extend, "UpdateItem")?;
+        self.inner
+            .extend_lease(key, lease_v, new_lease_v, expiry)
+            .await
+    }
+
+    async fn adopt_lease(
+        &self,
+        key: &str,
+        lease_v: Uuid,
+        new_lease_v: Uuid,
+        expiry: i64,
+        owner: &str,
+    ) -> Result<(), Error> {
+        self.inner
+            .adopt_lease(key, lease_v, new_lease_v, expiry, owner)
+            .await
+    }
+
+    async fn delete_lease(&self, key: &str, lease_v: Uuid) -> Result<(), Error> {
+        self.call(Op::Delete, "DeleteItem")?;
+        self.inner.delete_lease(key, lease_v).await
+    }
+
+    async fn put_leases(&self, leases: Vec<NewLease>) -> Result<Option<Vec<u64>>, Error> {
+        self.inner.put_leases(leases).await
+    }
+
+    async fn extend_leases(&self, leases: &[(&str, Uuid, Uuid)], expiry: i64) -> Result<(), Error> {
+        self.call(Op::Extend, "TransactWriteItems")?;
+        self.inner.extend_leases(leases, expiry).await
+    }
+
+    async fn delete_leases(&self, leases: &[(&str, Uuid)]) -> Result<(), Error> {
+        self.call(Op::Delete, "TransactWriteItems")?;
+        self.inner.delete_leases(leases).await
+    }
+
+    async fn put_ticket(
+        &self,
+        key: &str,
+        ticket: &str,
+        expiry: i64,
+    ) -> Result<BTreeMap<String, i64>, Error> {
+        self.inner.put_ticket(key, ticket, expiry).await
+    }
+
+    async fn delete_tickets(&self, key: &str, tickets: &[&str]) -> Result<(), Error> {
+        self.inner.delete_tickets(key, tickets).await
+    }
+
+    async fn put_shared_lease(&self, lease: NewLease) -> Result<Option<u64>, Error> {
+        self.inner.put_shared_lease(lease).await
+    }
+
+    async fn extend_shared_lease(
+        &self,
+        key: &str,
+        lease_v: Uuid,
+        new_lease_v: Uuid,
+        expiry: i64,
+        now: i64,
+    ) -> Result<(), Error> {
+        self.call(Op::Extend, "UpdateItem")?;
+        self.inner
+            .extend_shared_lease(key, lease_v, new_lease_v, expiry, now)
+            .await
+    }
+
+    async fn delete_shared_lease(&self, key: &str, lease_v: Uuid) -> Result<(), Error> {
+        self.call(Op::Delete, "UpdateItem")?;
+        self.inner.delete_shared_lease(key, lease_v).await
+    }
+
+    async fn revoke_lease(
+        &self,
+        key: &str,
+        lease_v: Option<Uuid>,
+        revoked_by: &str,
+        expiry: i64,
+    ) -> Result<(), Error> {
+        self.inner
+            .revoke_lease(key, lease_v, revoked_by, expiry)
+            .await
+    }
+
+    async fn get_lease(&self, key: &str) -> Result<Option<LeaseInfo>, Error> {
+        self.inner.get_lease(key).await
+    }
+
+    fn list_leases(&self, options: &ListOptions) -> BoxStream<'static, Result<LeaseInfo, Error>> {
+        self.inner.list_leases(options)
+    }
+
+    async fn check_schema(&self) -> Result<(), Error> {
+        self.inner.check_schema().await
+    }
+}
@@ -1,3 +1,6 @@
+#[cfg(feature = "memory")]
+#[allow(dead_code)]
+pub mod faulty;
 pub mod retry;
 
 use aws_config::BehaviorVersion;